@@ -1,21 +1,238 @@
-use chrobry_core::generate;
-use clap::{App, Arg};
+use chrobry_core::{
+    ast::Ast,
+    check_asts,
+    export::{export_json_schema, export_typescript},
+    generate_from_asts, parse_resolved,
+    parser::{parse, parse_imported, parse_model, resolve_imports},
+    processor::{EmitOrder, Options, TypeOrder, DEFAULT_OUTPUT},
+    regions::preserve_regions,
+    stdlib::with_std,
+    write_if_changed,
+};
+use clap::{App, AppSettings, Arg, ArgMatches, ErrorKind, SubCommand};
+use similar::TextDiff;
 use std::{
     collections::HashMap,
-    fs::{read_to_string, write},
+    fmt,
+    fs::{create_dir_all, metadata, read_to_string},
+    io::{stdin, stdout, Read, Write},
+    path::{Path, PathBuf},
+    process::exit,
+    thread::sleep,
+    time::{Duration, SystemTime},
 };
 
+const WATCH_INTERVAL: u64 = 500;
+const STD_STREAM: &str = "-";
+const EXIT_SUCCESS: i32 = 0;
+const EXIT_STALE: i32 = 1;
+const EXIT_WARNINGS: i32 = 6;
+
+enum Error {
+    Usage(String),
+    Input(String),
+    Template(String),
+    Output(String),
+}
+
+impl Error {
+    fn code(&self) -> i32 {
+        match self {
+            Error::Usage(_) => 2,
+            Error::Input(_) => 3,
+            Error::Template(_) => 4,
+            Error::Output(_) => 5,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Usage(message) => write!(f, "Usage error: {}", message),
+            Error::Input(message) => write!(f, "Input error: {}", message),
+            Error::Template(message) => write!(f, "Template error: {}", message),
+            Error::Output(message) => write!(f, "Output error: {}", message),
+        }
+    }
+}
+
+struct Config<'a> {
+    entry: &'a str,
+    input: Option<String>,
+    output: Option<&'a str>,
+    output_dir: PathBuf,
+    options: Options,
+    variables: HashMap<String, String>,
+    variable_files: Vec<(String, String)>,
+    models: Vec<&'a str>,
+    schemas: Vec<&'a str>,
+    check: bool,
+    report: bool,
+    list: bool,
+}
+
 fn main() {
-    let matches = App::new(env!("CARGO_PKG_NAME"))
+    match execute() {
+        Ok(EXIT_SUCCESS) => {}
+        Ok(code) => exit(code),
+        Err(error) => {
+            eprintln!("{}", error);
+            exit(error.code());
+        }
+    }
+}
+
+fn app() -> App<'static, 'static> {
+    App::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
         .author(env!("CARGO_PKG_AUTHORS"))
         .about(env!("CARGO_PKG_DESCRIPTION"))
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .subcommand(
+            SubCommand::with_name("check")
+                .alias("lint")
+                .about("Validates template without generating and reports suspicious definitions")
+                .arg(
+                    Arg::with_name("entry")
+                        .short("e")
+                        .long("entry")
+                        .value_name("FILE")
+                        .help("Chrobry template entry file name (`-` reads standard input)")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("variable")
+                        .short("v")
+                        .long("var")
+                        .value_name("NAME=VALUE")
+                        .help("Key-value pair for variable passed into generator")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("variable-file")
+                        .short("f")
+                        .long("file")
+                        .value_name("NAME=FILE")
+                        .help("Key-value pair for variable content got from the file passed into generator")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("model")
+                        .short("m")
+                        .long("model")
+                        .value_name("FILE")
+                        .help("JSON file with data model (serialized AST) merged with entry file")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("schema")
+                        .long("schema")
+                        .value_name("FILE")
+                        .help("JSON Schema (`.json`), Protocol Buffers (`.proto`) or Rust (`.rs`) file with data types merged with entry file")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("forward")
+                        .long("forward")
+                        .value_name("NAME")
+                        .help("Behaviour emitted for all types before any other behaviour")
+                        .takes_value(true)
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("dump-ast")
+                .about("Prints parsed template definitions as JSON")
+                .arg(
+                    Arg::with_name("entry")
+                        .short("e")
+                        .long("entry")
+                        .value_name("FILE")
+                        .help("Chrobry template entry file name (`-` reads standard input)")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("JSON file name (`-` or none writes to standard output)")
+                        .takes_value(true)
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("no-imports")
+                        .long("no-imports")
+                        .help("Dump entry file alone without merging imported files"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Exports data types as JSON Schema or TypeScript declarations")
+                .arg(
+                    Arg::with_name("entry")
+                        .short("e")
+                        .long("entry")
+                        .value_name("FILE")
+                        .help("Chrobry template, JSON Schema, Protocol Buffers or Rust entry file name (`-` reads Chrobry template from standard input)")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Exported file name (`-` or none writes to standard output)")
+                        .takes_value(true)
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Format of exported file")
+                        .takes_value(true)
+                        .possible_values(&["json-schema", "typescript"])
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("type")
+                        .short("t")
+                        .long("type")
+                        .value_name("NAME=TARGET")
+                        .help("Mapping of external type to target type, `$1`, `$2`, ... stand for generic arguments")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("types")
+                        .long("types")
+                        .value_name("FILE")
+                        .help("JSON file with object mapping external types to target types")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(false),
+                ),
+        )
         .arg(
             Arg::with_name("entry")
                 .short("e")
                 .long("entry")
                 .value_name("FILE")
-                .help("Chrobry template entry file name")
+                .help("Chrobry template entry file name (`-` reads standard input)")
                 .takes_value(true)
                 .required(true),
         )
@@ -24,9 +241,18 @@ fn main() {
                 .short("o")
                 .long("output")
                 .value_name("FILE")
-                .help("Chrobry generated file name")
+                .help("Chrobry generated file name (`-` or none writes to standard output)")
                 .takes_value(true)
-                .required(true),
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("output-dir")
+                .short("d")
+                .long("output-dir")
+                .value_name("DIR")
+                .help("Directory for named outputs (defaults to output file directory)")
+                .takes_value(true)
+                .required(false),
         )
         .arg(
             Arg::with_name("separator")
@@ -57,55 +283,536 @@ fn main() {
                 .multiple(true)
                 .required(false),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("model")
+                .short("m")
+                .long("model")
+                .value_name("FILE")
+                .help("JSON file with data model (serialized AST) merged with entry file")
+                .takes_value(true)
+                .multiple(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("schema")
+                .long("schema")
+                .value_name("FILE")
+                .help("JSON Schema (`.json`), Protocol Buffers (`.proto`) or Rust (`.rs`) file with data types merged with entry file")
+                .takes_value(true)
+                .multiple(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("order")
+                .long("order")
+                .value_name("ORDER")
+                .help("Order of generated types")
+                .takes_value(true)
+                .possible_values(&["declaration", "dependency"])
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("emit")
+                .long("emit")
+                .value_name("ORDER")
+                .help(
+                    "Order of generated behaviours: `type`, `behaviour` or comma separated list of behaviour names",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("per-type")
+                .long("per-type")
+                .value_name("TEMPLATE")
+                .help("File name template of separate output generated for each type, e.g. `%{ $TYPENAME | snake }%.rs`")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("list")
+                .short("l")
+                .long("list")
+                .help("Print paths of generated files"),
+        )
+        .arg(
+            Arg::with_name("report")
+                .long("report")
+                .help("Report whether each output file was changed or left untouched"),
+        )
+        .arg(
+            Arg::with_name("watch")
+                .short("w")
+                .long("watch")
+                .help("Regenerate whenever entry, imported or variable files change"),
+        )
+        .arg(
+            Arg::with_name("check")
+                .long("check")
+                .help("Do not write anything, fail with diff when generated files are stale"),
+        )
+        .arg(
+            Arg::with_name("forward")
+                .long("forward")
+                .value_name("NAME")
+                .help("Behaviour emitted for all types before any other behaviour")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("max-errors")
+                .long("max-errors")
+                .value_name("NUMBER")
+                .help("Maximum number of reported template errors (0 reports all)")
+                .takes_value(true)
+                .required(false),
+        )
+}
+
+fn exit_code(success: bool, failure: i32) -> i32 {
+    if success {
+        EXIT_SUCCESS
+    } else {
+        failure
+    }
+}
+
+fn execute() -> Result<i32, Error> {
+    let matches = match app().get_matches_safe() {
+        Ok(matches) => matches,
+        Err(error) => match error.kind {
+            ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => error.exit(),
+            _ => {
+                let message = error.message.trim_start_matches("error: ").to_owned();
+                return Err(Error::Usage(message));
+            }
+        },
+    };
+    if let Some(matches) = matches.subcommand_matches("check") {
+        return lint(matches).map(|clean| exit_code(clean, EXIT_WARNINGS));
+    }
+    if let Some(matches) = matches.subcommand_matches("dump-ast") {
+        return dump_ast(matches).map(|fresh| exit_code(fresh, EXIT_STALE));
+    }
+    if let Some(matches) = matches.subcommand_matches("export") {
+        return export(matches).map(|fresh| exit_code(fresh, EXIT_STALE));
+    }
     let entry = matches.value_of("entry").unwrap();
-    let output = matches.value_of("output").unwrap();
+    let output = matches
+        .value_of("output")
+        .filter(|output| *output != STD_STREAM);
+    let output_dir = match (matches.value_of("output-dir"), output) {
+        (Some(dir), _) => Path::new(dir).to_path_buf(),
+        (None, Some(output)) => Path::new(output)
+            .parent()
+            .map(|dir| dir.to_path_buf())
+            .unwrap_or_default(),
+        (None, None) => PathBuf::default(),
+    };
+    let watch = matches.is_present("watch");
+    let input = if entry == STD_STREAM {
+        if watch {
+            return Err(Error::Usage("Could not watch standard input".to_owned()));
+        }
+        let mut input = String::new();
+        stdin()
+            .read_to_string(&mut input)
+            .map_err(|error| Error::Input(format!("Could not read standard input: {}", error)))?;
+        Some(input)
+    } else {
+        None
+    };
     let separator = match matches.value_of("separator") {
-        Some(num) => num.parse::<usize>().unwrap(),
+        Some(number) => number.parse::<usize>().map_err(|_| {
+            Error::Usage(format!(
+                "Separator must be a number of new lines, got: `{}`",
+                number
+            ))
+        })?,
         None => 1,
     };
     let separator = "\n".repeat(separator);
-    let mut variables = if let Some(variables) = matches.values_of("variable") {
-        variables
-            .filter_map(|variable| {
-                let parts = variable.split("=").collect::<Vec<_>>();
-                if parts.len() == 2 {
-                    Some((parts[0].to_owned(), parts[1].to_owned()))
-                } else {
-                    None
-                }
-            })
-            .collect::<HashMap<_, _>>()
+    let max_errors = match matches.value_of("max-errors") {
+        Some(number) => number.parse::<usize>().map_err(|_| {
+            Error::Usage(format!(
+                "Maximum number of errors must be a number, got: `{}`",
+                number
+            ))
+        })?,
+        None => Options::default().max_errors,
+    };
+    let type_order = match matches.value_of("order") {
+        Some("dependency") => TypeOrder::Dependency,
+        _ => TypeOrder::Declaration,
+    };
+    let emit_order = match matches.value_of("emit") {
+        None | Some("type") => EmitOrder::Type,
+        Some("behaviour") => EmitOrder::Behaviour,
+        Some(names) => EmitOrder::Explicit(
+            names
+                .split(',')
+                .map(|name| name.trim().to_owned())
+                .filter(|name| !name.is_empty())
+                .collect::<Vec<_>>(),
+        ),
+    };
+    let options = Options {
+        separator,
+        type_order,
+        emit_order,
+        forward: matches.value_of("forward").map(str::to_owned),
+        per_type: matches.value_of("per-type").map(str::to_owned),
+        max_errors,
+    };
+    let config = Config {
+        entry,
+        input,
+        output,
+        output_dir,
+        options,
+        variables: parse_pairs(&matches, "variable")?
+            .into_iter()
+            .collect::<HashMap<_, _>>(),
+        variable_files: parse_pairs(&matches, "variable-file")?,
+        models: values(&matches, "model"),
+        schemas: values(&matches, "schema"),
+        check: matches.is_present("check"),
+        report: matches.is_present("report"),
+        list: matches.is_present("list"),
+    };
+    if watch {
+        loop {
+            let mut watched = vec![];
+            if let Err(error) = run(&config, &mut watched) {
+                eprintln!("{}", error);
+            }
+            let times = modification_times(&watched);
+            while modification_times(&watched) == times {
+                sleep(Duration::from_millis(WATCH_INTERVAL));
+            }
+        }
+    }
+    run(&config, &mut vec![]).map(|fresh| exit_code(fresh, EXIT_STALE))
+}
+
+fn lint(matches: &ArgMatches) -> Result<bool, Error> {
+    let (content, root) = read_entry(matches.value_of("entry").unwrap())?;
+    let options = Options {
+        forward: matches.value_of("forward").map(str::to_owned),
+        ..Default::default()
+    };
+    let variables = parse_pairs(matches, "variable")?
+        .into_iter()
+        .chain(parse_pairs(matches, "variable-file")?)
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    let mut asts = vec![parse(&content).map_err(Error::Template)?];
+    asts.extend(read_data_asts(
+        &values(matches, "model"),
+        &values(matches, "schema"),
+        &mut vec![],
+    )?);
+    let mut import_error = None;
+    let warnings = check_asts(
+        &asts,
+        &options,
+        &variables,
+        with_std(|path| read_import(&root.join(path), &mut import_error)),
+    )
+    .map_err(|error| template_error(error, import_error.take()))?;
+    for warning in &warnings {
+        eprintln!("warning: {}", warning);
+    }
+    Ok(warnings.is_empty())
+}
+
+fn dump_ast(matches: &ArgMatches) -> Result<bool, Error> {
+    let (content, root) = read_entry(matches.value_of("entry").unwrap())?;
+    let ast = if matches.is_present("no-imports") {
+        parse(&content).map_err(Error::Template)?
     } else {
-        HashMap::new()
+        let mut import_error = None;
+        parse_resolved(
+            &content,
+            with_std(|path| read_import(&root.join(path), &mut import_error)),
+        )
+        .map_err(|error| template_error(error, import_error.take()))?
     };
-    if let Some(files) = matches.values_of("variable-file") {
-        variables.extend(
-            files
-                .filter_map(|variable| {
-                    let parts = variable.split("=").collect::<Vec<_>>();
-                    if parts.len() == 2 {
-                        let entry = parts[1].to_owned();
-                        let content = read_to_string(&entry).unwrap_or_else(|error| {
-                            panic!(
-                                "Could not open variable content file: {} | {:?}",
-                                entry, error
-                            )
-                        });
-                        Some((parts[0].to_owned(), content))
-                    } else {
-                        None
-                    }
-                })
-                .collect::<HashMap<_, _>>(),
-        )
-    }
-    let content = read_to_string(entry)
-        .unwrap_or_else(|error| panic!("Could not open entry file: {} | {:?}", entry, error));
-    let content = match generate(&content, &separator, variables, |_| Ok("".to_owned())) {
-        Ok(content) => content,
-        Err(error) => panic!("{}", error),
+    let mut json = serde_json::to_string_pretty(&ast)
+        .map_err(|error| Error::Output(format!("Could not serialize AST: {}", error)))?;
+    json.push('\n');
+    write_output(matches, &json)
+}
+
+fn export(matches: &ArgMatches) -> Result<bool, Error> {
+    let entry = matches.value_of("entry").unwrap();
+    let (content, root) = read_entry(entry)?;
+    let ast = parse_imported(entry, &content).map_err(Error::Template)?;
+    let mut import_error = None;
+    let resolved = {
+        let mut on_import = with_std(|path| read_import(&root.join(path), &mut import_error));
+        resolve_imports(&ast, &mut on_import)
     };
-    write(output, &content)
-        .unwrap_or_else(|error| panic!("Could not write output file: {} | {:?}", output, error));
+    let ast = resolved.map_err(|error| template_error(error, import_error.take()))?;
+    let mut types = HashMap::new();
+    for path in matches.values_of("types").into_iter().flatten() {
+        let content = read_to_string(path).map_err(|error| {
+            Error::Input(format!("Could not open types file `{}`: {}", path, error))
+        })?;
+        let mapping = serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&content)
+            .map_err(|error| {
+                Error::Input(format!("Could not parse types file `{}`: {}", path, error))
+            })?;
+        for (name, target) in mapping {
+            let target = match target {
+                serde_json::Value::String(target) => target,
+                target => target.to_string(),
+            };
+            types.insert(name, target);
+        }
+    }
+    types.extend(parse_pairs(matches, "type")?);
+    let exported = match matches.value_of("format") {
+        Some("json-schema") => export_json_schema(&ast, &types),
+        _ => export_typescript(&ast, &types),
+    }
+    .map_err(|error| {
+        let unmapped = error
+            .lines()
+            .all(|line| line.starts_with("External type `") && line.ends_with(" has no mapping"));
+        if unmapped {
+            Error::Usage(error)
+        } else {
+            Error::Template(error)
+        }
+    })?;
+    write_output(matches, &exported)
+}
+
+fn write_output(matches: &ArgMatches, content: &str) -> Result<bool, Error> {
+    match matches
+        .value_of("output")
+        .filter(|output| *output != STD_STREAM)
+    {
+        Some(output) => write_if_changed(output, content)
+            .map(|_| ())
+            .map_err(|error| {
+                Error::Output(format!(
+                    "Could not write output file `{}`: {}",
+                    output, error
+                ))
+            })?,
+        None => stdout().write_all(content.as_bytes()).map_err(|error| {
+            Error::Output(format!("Could not write standard output: {}", error))
+        })?,
+    }
+    Ok(true)
+}
+
+fn read_entry(entry: &str) -> Result<(String, PathBuf), Error> {
+    if entry == STD_STREAM {
+        let mut input = String::new();
+        stdin()
+            .read_to_string(&mut input)
+            .map_err(|error| Error::Input(format!("Could not read standard input: {}", error)))?;
+        return Ok((input, PathBuf::default()));
+    }
+    let content = read_to_string(entry).map_err(|error| {
+        Error::Input(format!("Could not open entry file `{}`: {}", entry, error))
+    })?;
+    let root = Path::new(entry)
+        .parent()
+        .map(|dir| dir.to_path_buf())
+        .unwrap_or_default();
+    Ok((content, root))
+}
+
+fn read_import(path: &Path, import_error: &mut Option<String>) -> Result<String, String> {
+    read_to_string(path).map_err(|error| {
+        let message = format!(
+            "Could not open imported file `{}`: {}",
+            path.to_string_lossy(),
+            error
+        );
+        *import_error = Some(message.clone());
+        message
+    })
+}
+
+fn template_error(error: String, import_error: Option<String>) -> Error {
+    match import_error {
+        Some(message) => Error::Input(message),
+        None => Error::Template(error),
+    }
+}
+
+fn parse_pairs(matches: &ArgMatches, name: &str) -> Result<Vec<(String, String)>, Error> {
+    match matches.values_of(name) {
+        Some(values) => values
+            .map(|value| match value.split_once('=') {
+                Some((key, value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+                _ => Err(Error::Usage(format!(
+                    "Expected `NAME=VALUE` pair, got: `{}`",
+                    value
+                ))),
+            })
+            .collect::<Result<Vec<_>, _>>(),
+        None => Ok(vec![]),
+    }
+}
+
+fn values<'a>(matches: &'a ArgMatches, name: &str) -> Vec<&'a str> {
+    matches
+        .values_of(name)
+        .map(|values| values.collect::<Vec<_>>())
+        .unwrap_or_default()
+}
+
+fn read_data_asts(
+    models: &[&str],
+    schemas: &[&str],
+    watched: &mut Vec<PathBuf>,
+) -> Result<Vec<Ast>, Error> {
+    let mut result = vec![];
+    for path in models {
+        watched.push(PathBuf::from(path));
+        let content = read_to_string(path).map_err(|error| {
+            Error::Input(format!("Could not open model file `{}`: {}", path, error))
+        })?;
+        let model = parse_model(&content).map_err(|error| {
+            Error::Input(format!("Could not parse model file `{}`: {}", path, error))
+        })?;
+        result.push(model);
+    }
+    for path in schemas {
+        watched.push(PathBuf::from(path));
+        let content = read_to_string(path).map_err(|error| {
+            Error::Input(format!("Could not open schema file `{}`: {}", path, error))
+        })?;
+        let schema = parse_imported(path, &content).map_err(|error| {
+            Error::Input(format!(
+                "Could not import schema file `{}`: {}",
+                path, error
+            ))
+        })?;
+        result.push(schema);
+    }
+    Ok(result)
+}
+
+fn modification_times(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| metadata(path).and_then(|m| m.modified()).ok())
+        .collect::<Vec<_>>()
+}
+
+fn run(config: &Config, watched: &mut Vec<PathBuf>) -> Result<bool, Error> {
+    let mut variables = config.variables.clone();
+    for (name, path) in &config.variable_files {
+        watched.push(PathBuf::from(path));
+        let content = read_to_string(path).map_err(|error| {
+            Error::Input(format!(
+                "Could not open variable content file `{}`: {}",
+                path, error
+            ))
+        })?;
+        variables.insert(name.to_owned(), content);
+    }
+    let (content, root) = match &config.input {
+        Some(input) => (input.to_owned(), PathBuf::default()),
+        None => {
+            watched.push(PathBuf::from(config.entry));
+            read_entry(config.entry)?
+        }
+    };
+    let mut asts = vec![parse(&content).map_err(Error::Template)?];
+    asts.extend(read_data_asts(&config.models, &config.schemas, watched)?);
+    let mut import_error = None;
+    let outputs = generate_from_asts(
+        &asts,
+        &config.options,
+        variables,
+        with_std(|path| {
+            let path = root.join(path);
+            watched.push(path.clone());
+            read_import(&path, &mut import_error)
+        }),
+    )
+    .map_err(|error| template_error(error, import_error.take()))?;
+    let mut fresh = true;
+    let mut names = outputs.keys().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        let mut content = outputs[name].to_owned();
+        let path = match (name == DEFAULT_OUTPUT, config.output) {
+            (true, Some(output)) => Path::new(output).to_path_buf(),
+            (true, None) => {
+                if config.check {
+                    return Err(Error::Usage(
+                        "Could not check output written to standard output".to_owned(),
+                    ));
+                }
+                stdout().write_all(content.as_bytes()).map_err(|error| {
+                    Error::Output(format!("Could not write standard output: {}", error))
+                })?;
+                continue;
+            }
+            (false, _) => config.output_dir.join(name),
+        };
+        let display = path.to_string_lossy();
+        let previous = if path.is_file() {
+            let previous = read_to_string(&path).map_err(|error| {
+                Error::Output(format!(
+                    "Could not read previous output file `{}`: {}",
+                    display, error
+                ))
+            })?;
+            content = preserve_regions(&previous, &content)
+                .map_err(|error| Error::Output(format!("{}: {}", display, error)))?;
+            Some(previous)
+        } else {
+            None
+        };
+        if config.check {
+            let previous = previous.unwrap_or_default();
+            if previous != content {
+                fresh = false;
+                print!(
+                    "{}",
+                    TextDiff::from_lines(&previous, &content)
+                        .unified_diff()
+                        .header(&display, &display)
+                );
+            }
+            continue;
+        }
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir).map_err(|error| {
+                Error::Output(format!(
+                    "Could not create output directory `{}`: {}",
+                    dir.to_string_lossy(),
+                    error
+                ))
+            })?;
+        }
+        let changed = write_if_changed(&path, &content).map_err(|error| {
+            Error::Output(format!(
+                "Could not write output file `{}`: {}",
+                display, error
+            ))
+        })?;
+        if config.report {
+            let status = if changed { "changed" } else { "unchanged" };
+            eprintln!("{}: {}", status, display);
+        }
+        if config.list {
+            if config.output.is_some() {
+                println!("{}", display);
+            } else {
+                eprintln!("{}", display);
+            }
+        }
+    }
+    Ok(fresh)
 }
@@ -1,13 +1,23 @@
-use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub type AstTag = (String, BTreeMap<String, AstValue>);
+
+pub const FIELD_LABEL: &str = "label";
+pub const FIELD_KEY: &str = "key";
 
 #[derive(Parser)]
 #[grammar = "grammar.pest"]
 pub struct AstParser;
 
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Ast {
     pub imports: Vec<String>,
-    pub injects: Vec<AstCode>,
+    pub aliases: Vec<AstAlias>,
+    pub injects: Vec<AstInject>,
     pub replacements: Vec<AstReplace>,
     pub externs: Vec<AstExtern>,
     pub structs: Vec<AstStruct>,
@@ -15,71 +25,214 @@ pub struct Ast {
     pub implementations: Vec<AstImplementation>,
 }
 
-// impl Ast {
-//     pub fn merge_with(&mut self, ast: &Ast) {
-//
-//     }
-// }
+impl Ast {
+    pub fn merge_with(&mut self, ast: &Ast) {
+        self.aliases.extend(ast.aliases.iter().cloned());
+        self.injects.extend(ast.injects.iter().cloned());
+        self.replacements.extend(ast.replacements.iter().cloned());
+        self.externs.extend(ast.externs.iter().cloned());
+        self.structs.extend(ast.structs.iter().cloned());
+        self.enums.extend(ast.enums.iter().cloned());
+        self.implementations
+            .extend(ast.implementations.iter().cloned());
+    }
+}
 
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AstInject {
+    pub output: Option<String>,
+    pub code: AstCode,
+}
+
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct AstReplace {
     pub pattern: String,
     pub template: AstCode,
 }
 
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct AstExtern {
     pub types: Vec<String>,
     pub implementations: Vec<(String, AstCode)>,
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AstAlias {
+    pub name: String,
+    pub type_: AstType,
+}
+
+#[derive(Debug, Default, Clone, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AstType {
+    #[default]
     None,
     Extern(String),
     Local(String),
 }
 
-impl Default for AstType {
+impl std::fmt::Display for AstType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AstType::None => Ok(()),
+            AstType::Extern(name) => write!(f, "{}", name),
+            AstType::Local(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AstStruct {
+    pub tags: Vec<AstTag>,
+    pub name: String,
+    pub base: Option<String>,
+    pub fields: Vec<AstField>,
+}
+
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AstField {
+    pub tags: Vec<AstTag>,
+    pub name: String,
+    pub type_: AstType,
+    pub default: Option<String>,
+}
+
+impl AstField {
+    pub fn label(&self) -> Option<&str> {
+        self.tags
+            .iter()
+            .find_map(|(_, params)| match params.get(FIELD_LABEL) {
+                Some(AstValue::String(label)) => Some(label.as_str()),
+                _ => None,
+            })
+    }
+
+    pub fn labeled_type(&self) -> AstType {
+        let key = self
+            .tags
+            .iter()
+            .find_map(|(_, params)| params.get(FIELD_KEY));
+        match (self.label(), key) {
+            (Some("map"), Some(key)) => AstType::Extern(format!("map<{}, {}>", key, self.type_)),
+            (Some(label), _) if label == "optional" || label == "repeated" => {
+                AstType::Extern(format!("{}<{}>", label, self.type_))
+            }
+            _ => self.type_.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AstValue {
+    String(String),
+    Number(String),
+    Bool(bool),
+    List(Vec<AstValue>),
+    Map(Vec<(String, AstValue)>),
+}
+
+impl Default for AstValue {
     fn default() -> Self {
-        Self::None
+        Self::String(Default::default())
     }
 }
 
-impl ToString for AstType {
-    fn to_string(&self) -> String {
+impl From<String> for AstValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<&str> for AstValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_owned())
+    }
+}
+
+impl std::fmt::Display for AstValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            AstType::None => "".to_owned(),
-            AstType::Extern(name) => name.to_owned(),
-            AstType::Local(name) => name.to_owned(),
+            AstValue::String(value) => write!(f, "{}", value),
+            AstValue::Number(value) => write!(f, "{}", value),
+            AstValue::Bool(value) => write!(f, "{}", value),
+            AstValue::List(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "|")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                Ok(())
+            }
+            AstValue::Map(items) => {
+                for (i, (key, value)) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "|")?;
+                    }
+                    write!(f, "{}={}", key, value)?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
 #[derive(Debug, Default, Clone)]
-pub struct AstStruct {
-    pub tags: Vec<(String, HashMap<String, String>)>,
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AstEnum {
+    pub tags: Vec<AstTag>,
     pub name: String,
-    pub fields: Vec<(String, AstType)>,
+    pub fields: Vec<AstEnumField>,
 }
 
 #[derive(Debug, Default, Clone)]
-pub struct AstEnum {
-    pub tags: Vec<(String, HashMap<String, String>)>,
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AstEnumField {
+    pub tags: Vec<AstTag>,
     pub name: String,
-    pub fields: Vec<String>,
 }
 
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct AstImplementation {
     pub target: AstImplementationTarget,
     pub name: String,
+    pub parameters: Option<Vec<AstImplementationParameter>>,
+    pub requires: Vec<String>,
+    pub output: Option<String>,
     pub where_rules: Vec<AstWhereRule>,
     pub code: AstCode,
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AstImplementationParameter {
+    pub name: String,
+    pub required: bool,
+    pub default: Option<AstValue>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AstImplementationTarget {
+    #[default]
     All,
     Struct,
     Enum,
@@ -87,63 +240,65 @@ pub enum AstImplementationTarget {
 
 impl AstImplementationTarget {
     pub fn is_valid(&self, other: AstImplementationTarget) -> bool {
-        match (self, other) {
-            (Self::All, _) | (Self::Struct, Self::Struct) | (Self::Enum, Self::Enum) => true,
-            _ => false,
-        }
-    }
-}
-
-impl Default for AstImplementationTarget {
-    fn default() -> Self {
-        Self::All
+        matches!(
+            (self, other),
+            (Self::All, _) | (Self::Struct, Self::Struct) | (Self::Enum, Self::Enum)
+        )
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AstWhereRule {
+    #[default]
     None,
     Exists(String),
     Is(AstWhereRuleIs),
     Impl(AstWhereRuleImpl),
 }
 
-impl Default for AstWhereRule {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct AstWhereRuleIs {
     pub variable: String,
     pub value: String,
 }
 
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct AstWhereRuleImpl {
     pub container: AstIn,
     pub implements: Vec<String>,
 }
 
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct AstCode(pub Vec<AstCodeChunk>);
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AstCodeChunk {
+    #[default]
     None,
     Content(String),
-    Variable(String),
+    Variable(AstCodeVariable),
     For(AstCodeFor),
 }
 
-impl Default for AstCodeChunk {
-    fn default() -> Self {
-        Self::None
-    }
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AstCodeVariable {
+    pub name: String,
+    pub filters: Vec<String>,
 }
 
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct AstCodeMatch {
     pub variables: Vec<String>,
     pub container: AstIn,
@@ -152,6 +307,8 @@ pub struct AstCodeMatch {
 }
 
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct AstCodeFor {
     pub variables: Vec<String>,
     pub container: AstIn,
@@ -159,15 +316,11 @@ pub struct AstCodeFor {
     pub code: AstCode,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AstIn {
+    #[default]
     None,
     Fields,
     Variable(String),
 }
-
-impl Default for AstIn {
-    fn default() -> Self {
-        Self::None
-    }
-}
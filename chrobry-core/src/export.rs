@@ -0,0 +1,419 @@
+use crate::{ast::*, processor::join_errors};
+use std::collections::HashMap;
+
+enum Mapped<'a> {
+    Local(String),
+    Extern(&'a str, Vec<Mapped<'a>>),
+}
+
+struct Mapper<'a> {
+    ast: &'a Ast,
+    types: &'a HashMap<String, String>,
+    errors: Vec<String>,
+}
+
+impl<'a> Mapper<'a> {
+    fn new(ast: &'a Ast, types: &'a HashMap<String, String>) -> Self {
+        Self {
+            ast,
+            types,
+            errors: vec![],
+        }
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.ast.structs.iter().any(|s| s.name == name)
+            || self.ast.enums.iter().any(|e| e.name == name)
+            || self.ast.aliases.iter().any(|a| a.name == name)
+    }
+
+    fn map(&mut self, type_: &AstType, owner: &str) -> Option<Mapped<'a>> {
+        match type_ {
+            AstType::Local(name) => Some(Mapped::Local(name.to_owned())),
+            AstType::Extern(name) => self.map_extern(name.trim(), owner),
+            AstType::None => {
+                self.errors.push(format!("Missing type of {}", owner));
+                None
+            }
+        }
+    }
+
+    fn map_extern(&mut self, name: &str, owner: &str) -> Option<Mapped<'a>> {
+        if self.is_local(name) {
+            return Some(Mapped::Local(name.to_owned()));
+        }
+        if let Some(template) = self.types.get(name) {
+            return Some(Mapped::Extern(template, vec![]));
+        }
+        if let Some((head, arguments)) = split_generic(name) {
+            if let Some(template) = self.types.get(head) {
+                let arguments = arguments
+                    .into_iter()
+                    .map(|argument| self.map_extern(argument, owner))
+                    .collect::<Option<Vec<_>>>()?;
+                return Some(Mapped::Extern(template, arguments));
+            }
+        }
+        self.errors.push(format!(
+            "External type `{}` used by {} has no mapping",
+            name, owner
+        ));
+        None
+    }
+
+    fn finish<T>(self, result: T) -> Result<T, String> {
+        if self.errors.is_empty() {
+            Ok(result)
+        } else {
+            Err(join_errors(self.errors, 0))
+        }
+    }
+}
+
+fn is_optional(field: &AstField) -> bool {
+    field.default.is_some() || field.label() == Some("optional")
+}
+
+fn split_generic(name: &str) -> Option<(&str, Vec<&str>)> {
+    let (head, rest) = name.split_once('<')?;
+    let rest = rest.strip_suffix('>')?;
+    let mut arguments = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in rest.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                arguments.push(rest[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    arguments.push(rest[start..].trim());
+    Some((head.trim(), arguments))
+}
+
+pub fn export_typescript(ast: &Ast, types: &HashMap<String, String>) -> Result<String, String> {
+    let mut mapper = Mapper::new(ast, types);
+    let mut result = vec![];
+    for struct_ in &ast.structs {
+        let mut code = match &struct_.base {
+            Some(base) => format!("export interface {} extends {} {{\n", struct_.name, base),
+            None => format!("export interface {} {{\n", struct_.name),
+        };
+        for field in &struct_.fields {
+            let owner = format!("field `{}` of struct `{}`", field.name, struct_.name);
+            if let Some(type_) = mapper.map(&field.labeled_type(), &owner) {
+                code.push_str(&format!(
+                    "  {}{}: {};\n",
+                    typescript_key(&field.name),
+                    if is_optional(field) { "?" } else { "" },
+                    render_typescript(&type_)
+                ));
+            }
+        }
+        code.push('}');
+        result.push(code);
+    }
+    for enum_ in &ast.enums {
+        let values = enum_
+            .fields
+            .iter()
+            .map(|field| format!("{:?}", field.name))
+            .collect::<Vec<_>>();
+        let values = if values.is_empty() {
+            "never".to_owned()
+        } else {
+            values.join(" | ")
+        };
+        result.push(format!("export type {} = {};", enum_.name, values));
+    }
+    for alias in &ast.aliases {
+        let owner = format!("alias `{}`", alias.name);
+        if let Some(type_) = mapper.map(&alias.type_, &owner) {
+            result.push(format!(
+                "export type {} = {};",
+                alias.name,
+                render_typescript(&type_)
+            ));
+        }
+    }
+    let mut result = result.join("\n\n");
+    result.push('\n');
+    mapper.finish(result)
+}
+
+fn typescript_key(name: &str) -> String {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .map(|c| c.is_alphabetic() || c == '_' || c == '$')
+        .unwrap_or(false)
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    if valid {
+        name.to_owned()
+    } else {
+        format!("{:?}", name)
+    }
+}
+
+fn render_typescript(type_: &Mapped) -> String {
+    match type_ {
+        Mapped::Local(name) => name.to_owned(),
+        Mapped::Extern(template, arguments) => arguments.iter().enumerate().rev().fold(
+            template.to_string(),
+            |result, (index, argument)| {
+                result.replace(&format!("${}", index + 1), &render_typescript(argument))
+            },
+        ),
+    }
+}
+
+#[cfg(feature = "serde")]
+pub const JSON_SCHEMA_DIALECT: &str = "http://json-schema.org/draft-07/schema#";
+
+#[cfg(feature = "serde")]
+pub fn export_json_schema(ast: &Ast, types: &HashMap<String, String>) -> Result<String, String> {
+    use serde_json::{json, Map, Value};
+
+    let mut mapper = Mapper::new(ast, types);
+    let mut definitions = Map::new();
+    for struct_ in &ast.structs {
+        let mut properties = Map::new();
+        let mut required = vec![];
+        for field in &struct_.fields {
+            let owner = format!("field `{}` of struct `{}`", field.name, struct_.name);
+            let mut schema = match mapper.map(&field.labeled_type(), &owner) {
+                Some(type_) => match render_json_schema(&type_) {
+                    Ok(schema) => schema,
+                    Err(error) => {
+                        mapper.errors.push(format!("{} in {}", error, owner));
+                        continue;
+                    }
+                },
+                None => continue,
+            };
+            match &field.default {
+                Some(default) => {
+                    let default = serde_json::from_str::<Value>(default)
+                        .unwrap_or_else(|_| Value::String(default.to_owned()));
+                    if schema.get("$ref").is_some() {
+                        schema = json!({ "allOf": [schema] });
+                    }
+                    if let Value::Object(schema) = &mut schema {
+                        schema.insert("default".to_owned(), default);
+                    }
+                }
+                None if !is_optional(field) => required.push(Value::String(field.name.to_owned())),
+                None => {}
+            }
+            properties.insert(field.name.to_owned(), schema);
+        }
+        let mut schema = Map::new();
+        if let Some(base) = &struct_.base {
+            schema.insert("allOf".to_owned(), json!([definition_ref(base)]));
+        }
+        schema.insert("type".to_owned(), json!("object"));
+        schema.insert("properties".to_owned(), Value::Object(properties));
+        if !required.is_empty() {
+            schema.insert("required".to_owned(), Value::Array(required));
+        }
+        definitions.insert(struct_.name.to_owned(), Value::Object(schema));
+    }
+    for enum_ in &ast.enums {
+        let values = enum_
+            .fields
+            .iter()
+            .map(|field| field.name.to_owned())
+            .collect::<Vec<_>>();
+        definitions.insert(
+            enum_.name.to_owned(),
+            json!({ "type": "string", "enum": values }),
+        );
+    }
+    for alias in &ast.aliases {
+        let owner = format!("alias `{}`", alias.name);
+        if let Some(type_) = mapper.map(&alias.type_, &owner) {
+            match render_json_schema(&type_) {
+                Ok(schema) => {
+                    definitions.insert(alias.name.to_owned(), schema);
+                }
+                Err(error) => mapper.errors.push(format!("{} in {}", error, owner)),
+            }
+        }
+    }
+    let schema = json!({
+        "$schema": JSON_SCHEMA_DIALECT,
+        "definitions": definitions,
+    });
+    let mut result = serde_json::to_string_pretty(&schema).map_err(|error| format!("{}", error))?;
+    result.push('\n');
+    mapper.finish(result)
+}
+
+#[cfg(feature = "serde")]
+fn definition_ref(name: &str) -> serde_json::Value {
+    serde_json::json!({ "$ref": format!("#/definitions/{}", name) })
+}
+
+#[cfg(feature = "serde")]
+fn render_json_schema(type_: &Mapped) -> Result<serde_json::Value, String> {
+    match type_ {
+        Mapped::Local(name) => Ok(definition_ref(name)),
+        Mapped::Extern(template, arguments) => {
+            let mut schema = serde_json::from_str(template).map_err(|error| {
+                format!("Mapping `{}` is not valid JSON Schema: {}", template, error)
+            })?;
+            let arguments = arguments
+                .iter()
+                .map(render_json_schema)
+                .collect::<Result<Vec<_>, _>>()?;
+            substitute_arguments(&mut schema, &arguments);
+            Ok(schema)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn substitute_arguments(schema: &mut serde_json::Value, arguments: &[serde_json::Value]) {
+    use serde_json::Value;
+
+    match schema {
+        Value::String(value) => {
+            let argument = value
+                .strip_prefix('$')
+                .and_then(|index| index.parse::<usize>().ok())
+                .and_then(|index| arguments.get(index.checked_sub(1)?));
+            if let Some(argument) = argument {
+                *schema = argument.clone();
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                substitute_arguments(item, arguments);
+            }
+        }
+        Value::Object(items) => {
+            for item in items.values_mut() {
+                substitute_arguments(item, arguments);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    const CONTENT: &str = r#"
+alias Ids = 'list<int>'
+
+struct Base {
+  id: 'int'
+}
+
+struct Person : Base {
+  name: 'string' = 'anonymous'
+  @Proto { label = 'optional' }
+  nick: 'string'
+  kind: Kind
+  ids: Ids
+}
+
+enum Kind {
+  Adult
+  Child
+}
+"#;
+
+    fn types(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, target)| (name.to_string(), target.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn export_typescript_declares_types() {
+        let ast = parse(CONTENT).unwrap();
+        let types = types(&[
+            ("int", "number"),
+            ("string", "string"),
+            ("list", "Array<$1>"),
+            ("optional", "$1 | null"),
+        ]);
+        assert_eq!(
+            export_typescript(&ast, &types).unwrap(),
+            "export interface Base {\n  id: number;\n}\n\n\
+             export interface Person extends Base {\n  name?: string;\n  nick?: string | null;\n  kind: Kind;\n  ids: Ids;\n}\n\n\
+             export type Kind = \"Adult\" | \"Child\";\n\n\
+             export type Ids = Array<number>;\n"
+        );
+    }
+
+    #[test]
+    fn export_reports_unmapped_types() {
+        let ast = parse(CONTENT).unwrap();
+        assert_eq!(
+            export_typescript(&ast, &types(&[("string", "string")])).unwrap_err(),
+            "External type `int` used by field `id` of struct `Base` has no mapping\n\
+             External type `optional<string>` used by field `nick` of struct `Person` has no mapping\n\
+             External type `list<int>` used by alias `Ids` has no mapping"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn export_json_schema_declares_types() {
+        let ast = parse(CONTENT).unwrap();
+        let types = types(&[
+            ("int", r#"{ "type": "integer" }"#),
+            ("string", r#"{ "type": "string" }"#),
+            ("list", r#"{ "type": "array", "items": "$1" }"#),
+            ("optional", r#"{ "oneOf": [{ "type": "null" }, "$1"] }"#),
+        ]);
+        let schema =
+            serde_json::from_str::<serde_json::Value>(&export_json_schema(&ast, &types).unwrap())
+                .unwrap();
+        assert_eq!(
+            schema,
+            serde_json::json!({
+                "$schema": JSON_SCHEMA_DIALECT,
+                "definitions": {
+                    "Base": {
+                        "type": "object",
+                        "properties": { "id": { "type": "integer" } },
+                        "required": ["id"]
+                    },
+                    "Person": {
+                        "allOf": [{ "$ref": "#/definitions/Base" }],
+                        "type": "object",
+                        "properties": {
+                            "name": { "type": "string", "default": "anonymous" },
+                            "nick": { "oneOf": [{ "type": "null" }, { "type": "string" }] },
+                            "kind": { "$ref": "#/definitions/Kind" },
+                            "ids": { "$ref": "#/definitions/Ids" }
+                        },
+                        "required": ["kind", "ids"]
+                    },
+                    "Kind": { "type": "string", "enum": ["Adult", "Child"] },
+                    "Ids": { "type": "array", "items": { "type": "integer" } }
+                }
+            })
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn export_json_schema_rejects_invalid_mapping() {
+        let ast = parse("struct Foo {\n  a: 'int'\n}").unwrap();
+        assert_eq!(
+            export_json_schema(&ast, &types(&[("int", "integer")])).unwrap_err(),
+            "Mapping `integer` is not valid JSON Schema: expected value at line 1 column 1 in field `a` of struct `Foo`"
+        );
+    }
+}
@@ -0,0 +1,416 @@
+use crate::ast::*;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+pub const TAGS_KEYWORD: &str = "x-chrobry";
+pub const SCHEMA_TAG: &str = "JsonSchema";
+
+pub fn import_json_schema(content: &str) -> Result<Ast, String> {
+    let schema = serde_json::from_str::<Value>(content).map_err(|error| format!("{}", error))?;
+    let mut ast = Ast::default();
+    for key in &["definitions", "$defs"] {
+        if let Some(definitions) = schema.get(key).and_then(Value::as_object) {
+            for (name, definition) in definitions {
+                import_definition(name, definition, &schema, &mut ast)?;
+            }
+        }
+    }
+    if let Some(title) = schema.get("title").and_then(Value::as_str) {
+        if schema.get("properties").is_some() || schema.get("enum").is_some() {
+            import_definition(&pascal_case(title), &schema, &schema, &mut ast)?;
+        }
+    }
+    Ok(ast)
+}
+
+fn import_definition(
+    name: &str,
+    definition: &Value,
+    root: &Value,
+    ast: &mut Ast,
+) -> Result<(), String> {
+    let tags = import_tags(name, definition)?;
+    if let Some(values) = definition.get("enum").and_then(Value::as_array) {
+        let fields = values
+            .iter()
+            .map(|value| match value {
+                Value::String(value) => Ok(AstEnumField {
+                    tags: vec![],
+                    name: value.to_owned(),
+                }),
+                value => Err(format!(
+                    "Enum `{}` has non-string value `{}` which is not supported",
+                    name, value
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        ast.enums.push(AstEnum {
+            tags,
+            name: name.to_owned(),
+            fields,
+        });
+        return Ok(());
+    }
+    let properties = match definition.get("properties").and_then(Value::as_object) {
+        Some(properties) => properties,
+        None => {
+            let type_ = import_type(name, definition, root, ast)?;
+            ast.aliases.push(AstAlias {
+                name: name.to_owned(),
+                type_,
+            });
+            return Ok(());
+        }
+    };
+    let required = definition
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|values| values.iter().filter_map(Value::as_str).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let mut fields = vec![];
+    for (field, property) in properties {
+        let owner = format!("{}{}", name, pascal_case(field));
+        let (type_, mut params) = import_labeled_type(&owner, property, root, ast)?;
+        if params.is_empty() && !required.contains(&field.as_str()) {
+            params.insert(FIELD_LABEL.to_owned(), AstValue::from("optional"));
+        }
+        let tags = if params.is_empty() {
+            vec![]
+        } else {
+            vec![(SCHEMA_TAG.to_owned(), params)]
+        };
+        let default = property.get("default").map(|value| match value {
+            Value::String(value) => value.to_owned(),
+            value => value.to_string(),
+        });
+        fields.push(AstField {
+            tags,
+            name: field.to_owned(),
+            type_,
+            default,
+        });
+    }
+    ast.structs.push(AstStruct {
+        tags,
+        name: name.to_owned(),
+        base: None,
+        fields,
+    });
+    Ok(())
+}
+
+fn import_type(
+    owner: &str,
+    schema: &Value,
+    root: &Value,
+    ast: &mut Ast,
+) -> Result<AstType, String> {
+    let (type_, params) = import_labeled_type(owner, schema, root, ast)?;
+    let field = AstField {
+        tags: vec![(SCHEMA_TAG.to_owned(), params)],
+        type_,
+        ..Default::default()
+    };
+    Ok(field.labeled_type())
+}
+
+fn import_labeled_type(
+    owner: &str,
+    schema: &Value,
+    root: &Value,
+    ast: &mut Ast,
+) -> Result<(AstType, BTreeMap<String, AstValue>), String> {
+    let mut params = BTreeMap::new();
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        return match resolve_reference(reference, root) {
+            Some(name) => Ok((AstType::Local(name), params)),
+            None => Err(format!(
+                "Reference `{}` used by `{}` could not be resolved, only references to `definitions` or `$defs` of the same document are supported",
+                reference, owner
+            )),
+        };
+    }
+    if schema.get("properties").is_some() || schema.get("enum").is_some() {
+        import_definition(owner, schema, root, ast)?;
+        return Ok((AstType::Local(owner.to_owned()), params));
+    }
+    let type_ = match schema.get("type") {
+        Some(Value::String(type_)) => type_.as_str(),
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|t| *t != "null")
+            .unwrap_or("null"),
+        _ => return Ok((AstType::Extern("any".to_owned()), params)),
+    };
+    let type_ = match type_ {
+        "array" => {
+            params.insert(FIELD_LABEL.to_owned(), AstValue::from("repeated"));
+            match schema.get("items") {
+                Some(items) => import_type(&format!("{}Item", owner), items, root, ast)?,
+                None => AstType::Extern("any".to_owned()),
+            }
+        }
+        "object" => match schema.get("additionalProperties") {
+            Some(Value::Object(_)) => {
+                params.insert(FIELD_LABEL.to_owned(), AstValue::from("map"));
+                params.insert(FIELD_KEY.to_owned(), AstValue::from("string"));
+                import_type(
+                    &format!("{}Value", owner),
+                    &schema["additionalProperties"],
+                    root,
+                    ast,
+                )?
+            }
+            _ => AstType::Extern("object".to_owned()),
+        },
+        type_ => AstType::Extern(type_.to_owned()),
+    };
+    Ok((type_, params))
+}
+
+fn resolve_reference(reference: &str, root: &Value) -> Option<String> {
+    if reference == "#" {
+        return root.get("title").and_then(Value::as_str).map(pascal_case);
+    }
+    let (key, name) = reference.strip_prefix("#/")?.split_once('/')?;
+    if key != "definitions" && key != "$defs" || name.contains('/') {
+        return None;
+    }
+    let name = name.replace("~1", "/").replace("~0", "~");
+    root.get(key)?.get(&name)?;
+    Some(name)
+}
+
+fn import_tags(name: &str, definition: &Value) -> Result<Vec<AstTag>, String> {
+    match definition.get(TAGS_KEYWORD) {
+        None => Ok(vec![]),
+        Some(Value::Array(tags)) => tags
+            .iter()
+            .map(|tag| match tag {
+                Value::String(tag) => Ok((tag.to_owned(), BTreeMap::new())),
+                tag => Err(format!("Tag `{}` of `{}` must be a trait name", tag, name)),
+            })
+            .collect(),
+        Some(Value::Object(tags)) => tags
+            .iter()
+            .map(|(tag, params)| match params {
+                Value::Object(params) => Ok((tag.to_owned(), import_parameters(params))),
+                Value::Null => Ok((tag.to_owned(), BTreeMap::new())),
+                _ => Err(format!(
+                    "Parameters of tag `{}` of `{}` must be an object",
+                    tag, name
+                )),
+            })
+            .collect(),
+        Some(_) => Err(format!(
+            "`{}` of `{}` must be a list of trait names or an object of trait parameters",
+            TAGS_KEYWORD, name
+        )),
+    }
+}
+
+fn import_parameters(params: &Map<String, Value>) -> BTreeMap<String, AstValue> {
+    params
+        .iter()
+        .map(|(key, value)| (key.to_owned(), import_value(value)))
+        .collect()
+}
+
+fn import_value(value: &Value) -> AstValue {
+    match value {
+        Value::Null => AstValue::default(),
+        Value::Bool(value) => AstValue::Bool(*value),
+        Value::Number(value) => AstValue::Number(value.to_string()),
+        Value::String(value) => AstValue::String(value.to_owned()),
+        Value::Array(items) => AstValue::List(items.iter().map(import_value).collect()),
+        Value::Object(items) => AstValue::Map(
+            items
+                .iter()
+                .map(|(key, value)| (key.to_owned(), import_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn pascal_case(value: &str) -> String {
+    value
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_json_schema_builds_types() {
+        let ast = import_json_schema(
+            r##"{
+                "title": "person record",
+                "x-chrobry": { "Display": {}, "Describe": { "inherit": "Base" } },
+                "required": ["name", "kind"],
+                "properties": {
+                    "name": { "type": "string", "default": "anonymous" },
+                    "kind": { "$ref": "#/definitions/Kind" },
+                    "address": {
+                        "properties": { "city": { "type": "string" } },
+                        "required": ["city"]
+                    },
+                    "friends": { "type": "array", "items": { "$ref": "#/$defs/Friend" } }
+                },
+                "definitions": {
+                    "Kind": { "enum": ["adult", "child"], "x-chrobry": ["Display"] }
+                },
+                "$defs": {
+                    "Friend": { "type": ["string", "null"] }
+                }
+            }"##,
+        )
+        .unwrap();
+        assert_eq!(
+            ast.enums
+                .iter()
+                .map(|e| (e.name.as_str(), e.fields.len(), e.tags.len()))
+                .collect::<Vec<_>>(),
+            vec![("Kind", 2, 1)]
+        );
+        assert_eq!(ast.aliases.len(), 1);
+        assert_eq!(ast.aliases[0].name, "Friend");
+        assert_eq!(ast.aliases[0].type_, AstType::Extern("string".to_owned()));
+        let names = ast
+            .structs
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["PersonRecordAddress", "PersonRecord"]);
+        let person = &ast.structs[1];
+        assert_eq!(person.tags[1].0, "Describe");
+        assert_eq!(person.tags[1].1["inherit"], AstValue::from("Base"));
+        let fields = person
+            .fields
+            .iter()
+            .map(|f| {
+                (
+                    f.name.as_str(),
+                    f.type_.clone(),
+                    f.label(),
+                    f.default.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![
+                (
+                    "name",
+                    AstType::Extern("string".to_owned()),
+                    None,
+                    Some("anonymous")
+                ),
+                ("kind", AstType::Local("Kind".to_owned()), None, None),
+                (
+                    "address",
+                    AstType::Local("PersonRecordAddress".to_owned()),
+                    Some("optional"),
+                    None
+                ),
+                (
+                    "friends",
+                    AstType::Local("Friend".to_owned()),
+                    Some("repeated"),
+                    None
+                ),
+            ]
+        );
+        assert_eq!(
+            person.fields[2].labeled_type(),
+            AstType::Extern("optional<PersonRecordAddress>".to_owned())
+        );
+    }
+
+    #[test]
+    fn import_json_schema_labels_containers() {
+        let ast = import_json_schema(
+            r#"{
+                "definitions": {
+                    "Scores": {
+                        "required": ["values"],
+                        "properties": {
+                            "values": {
+                                "type": "object",
+                                "additionalProperties": { "type": "array", "items": { "type": "integer" } }
+                            },
+                            "tags": { "type": "array", "items": { "type": "string" } }
+                        }
+                    },
+                    "Matrix": { "type": "array", "items": { "type": "array", "items": { "type": "number" } } }
+                }
+            }"#,
+        )
+        .unwrap();
+        let fields = ast.structs[0]
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.type_.clone(), f.labeled_type()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![
+                (
+                    "values",
+                    AstType::Extern("repeated<integer>".to_owned()),
+                    AstType::Extern("map<string, repeated<integer>>".to_owned())
+                ),
+                (
+                    "tags",
+                    AstType::Extern("string".to_owned()),
+                    AstType::Extern("repeated<string>".to_owned())
+                ),
+            ]
+        );
+        assert_eq!(
+            ast.aliases[0].type_,
+            AstType::Extern("repeated<repeated<number>>".to_owned())
+        );
+    }
+
+    #[test]
+    fn import_json_schema_rejects_unresolved_references() {
+        for reference in &[
+            "#/definitions/a/Foo",
+            "other.json#/Foo",
+            "#/definitions/Missing",
+            "#/properties/Foo",
+        ] {
+            let content = format!(
+                r#"{{ "definitions": {{ "Foo": {{ "type": "string" }}, "Bar": {{ "$ref": "{}" }} }} }}"#,
+                reference
+            );
+            assert_eq!(
+                import_json_schema(&content).unwrap_err(),
+                format!(
+                    "Reference `{}` used by `Bar` could not be resolved, only references to `definitions` or `$defs` of the same document are supported",
+                    reference
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn import_json_schema_rejects_non_string_enum() {
+        assert_eq!(
+            import_json_schema(r#"{ "definitions": { "Level": { "enum": [1, 2] } } }"#)
+                .unwrap_err(),
+            "Enum `Level` has non-string value `1` which is not supported"
+        );
+    }
+}
@@ -2,12 +2,23 @@
 extern crate pest_derive;
 
 pub mod ast;
+pub mod export;
+#[cfg(feature = "serde")]
+pub mod json_schema;
+pub mod lint;
 pub mod parser;
 pub mod processor;
+pub mod proto;
+pub mod regions;
+#[cfg(feature = "rust")]
+pub mod rust;
+pub mod stdlib;
 
-use crate::parser::parse;
-use crate::processor::process;
-use std::collections::HashMap;
+use crate::ast::Ast;
+use crate::lint::lint;
+use crate::parser::{parse, resolve_imports};
+use crate::processor::{join_errors, process, Options, Outputs, DEFAULT_OUTPUT};
+use std::{collections::HashMap, fs, io, path::Path};
 
 pub fn generate<F>(
     content: &str,
@@ -15,9 +26,126 @@ pub fn generate<F>(
     variables: HashMap<String, String>,
     on_import: F,
 ) -> Result<String, String>
+where
+    F: FnMut(&str) -> Result<String, String>,
+{
+    let options = Options {
+        separator: separator.to_owned(),
+        ..Default::default()
+    };
+    let mut outputs = generate_with_options(content, &options, variables, on_import)?;
+    Ok(outputs.remove(DEFAULT_OUTPUT).unwrap_or_default())
+}
+
+pub fn generate_with_options<F>(
+    content: &str,
+    options: &Options,
+    variables: HashMap<String, String>,
+    on_import: F,
+) -> Result<Outputs, String>
 where
     F: FnMut(&str) -> Result<String, String>,
 {
     let ast = parse(content)?;
-    process(&ast, separator, variables, on_import)
+    process(&ast, options, variables, on_import)
+}
+
+pub fn generate_from_asts<F>(
+    asts: &[Ast],
+    options: &Options,
+    variables: HashMap<String, String>,
+    on_import: F,
+) -> Result<Outputs, String>
+where
+    F: FnMut(&str) -> Result<String, String>,
+{
+    process(&merge_asts(asts)?, options, variables, on_import)
+}
+
+fn merge_asts(asts: &[Ast]) -> Result<Ast, String> {
+    let mut ast = asts.first().cloned().unwrap_or_default();
+    let mut errors = vec![];
+    for other in asts.iter().skip(1) {
+        let names = other
+            .structs
+            .iter()
+            .map(|s| &s.name)
+            .chain(other.enums.iter().map(|e| &e.name));
+        for name in names {
+            if ast.structs.iter().any(|s| &s.name == name)
+                || ast.enums.iter().any(|e| &e.name == name)
+            {
+                errors.push(format!("Type `{}` is defined more than once", name));
+            }
+        }
+        ast.merge_with(other);
+    }
+    if !errors.is_empty() {
+        return Err(join_errors(errors, 0));
+    }
+    Ok(ast)
+}
+
+pub fn parse_resolved<F>(content: &str, mut on_import: F) -> Result<Ast, String>
+where
+    F: FnMut(&str) -> Result<String, String>,
+{
+    let mut ast = resolve_imports(&parse(content)?, &mut on_import)?;
+    ast.imports.clear();
+    Ok(ast)
+}
+
+pub fn check<F>(
+    content: &str,
+    options: &Options,
+    variables: &[String],
+    on_import: F,
+) -> Result<Vec<String>, String>
+where
+    F: FnMut(&str) -> Result<String, String>,
+{
+    let ast = parse(content)?;
+    lint(&ast, options, variables, on_import)
+}
+
+pub fn check_asts<F>(
+    asts: &[Ast],
+    options: &Options,
+    variables: &[String],
+    on_import: F,
+) -> Result<Vec<String>, String>
+where
+    F: FnMut(&str) -> Result<String, String>,
+{
+    lint(&merge_asts(asts)?, options, variables, on_import)
+}
+
+pub fn write_if_changed<P: AsRef<Path>>(path: P, content: &str) -> io::Result<bool> {
+    let path = path.as_ref();
+    if path.is_file() && fs::read(path)? == content.as_bytes() {
+        return Ok(false);
+    }
+    fs::write(path, content)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_from_asts_rejects_duplicate_types() {
+        let asts = [
+            parse("struct Foo {}\nenum Bar { A }").unwrap(),
+            parse("enum Foo { A }\nstruct Bar {}\nstruct Baz {}").unwrap(),
+        ];
+        let error = generate_from_asts(&asts, &Options::default(), HashMap::new(), |path| {
+            Err(format!("Unexpected import `{}`", path))
+        })
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "Type `Bar` is defined more than once\nType `Foo` is defined more than once"
+        );
+    }
 }
@@ -0,0 +1,423 @@
+use crate::{
+    ast::*,
+    parser::resolve_imports,
+    processor::{
+        apply_filter, get_impl_targets, validate_aliases, validate_forward, validate_struct_bases,
+        validate_type_impls, Options,
+    },
+};
+
+pub fn lint<F>(
+    ast: &Ast,
+    options: &Options,
+    variables: &[String],
+    mut on_import: F,
+) -> Result<Vec<String>, String>
+where
+    F: FnMut(&str) -> Result<String, String>,
+{
+    let ast = &resolve_imports(ast, &mut on_import)?;
+    let mut warnings = vec![];
+    validate_aliases(ast, &mut warnings);
+    validate_struct_bases(ast, &mut warnings);
+    validate_type_impls(ast, &get_impl_targets(ast), &mut warnings);
+    validate_forward(ast, options, &mut warnings);
+    lint_implementations(ast, options, &mut warnings);
+    lint_externs(ast, &mut warnings);
+    for inject in &ast.injects {
+        lint_code(&inject.code, variables, "inject", ast, &mut warnings);
+    }
+    for replace in &ast.replacements {
+        let owner = format!("replacement `{}`", replace.pattern);
+        let mut known = variables.to_vec();
+        known.extend((0..10).map(|i| format!("_{}", i)));
+        lint_code(&replace.template, &known, &owner, ast, &mut warnings);
+    }
+    for external in &ast.externs {
+        for (name, code) in &external.implementations {
+            let owner = format!("trait `{}` of external types", name);
+            let mut known = variables.to_vec();
+            known.push("TYPENAME".to_owned());
+            lint_code(code, &known, &owner, ast, &mut warnings);
+        }
+    }
+    for implementation in &ast.implementations {
+        let owner = format!(
+            "trait `{}` for {}",
+            implementation.name,
+            target_name(implementation.target)
+        );
+        let mut known = variables.to_vec();
+        known.push("TYPENAME".to_owned());
+        if implementation.target != AstImplementationTarget::Enum {
+            known.push("BASE".to_owned());
+        }
+        if let Some(parameters) = &implementation.parameters {
+            known.extend(parameters.iter().map(|p| p.name.to_owned()));
+        }
+        for (tags, target) in all_tags(ast) {
+            if implementation.target.is_valid(target) {
+                for (_, params) in tags.iter().filter(|(n, _)| n == &implementation.name) {
+                    known.extend(params.keys().cloned());
+                }
+            }
+        }
+        lint_where_rules(
+            &implementation.where_rules,
+            &mut known,
+            &owner,
+            ast,
+            &mut warnings,
+        );
+        lint_code(&implementation.code, &known, &owner, ast, &mut warnings);
+    }
+    let mut result = vec![];
+    for warning in warnings {
+        if !result.contains(&warning) {
+            result.push(warning);
+        }
+    }
+    Ok(result)
+}
+
+fn target_name(target: AstImplementationTarget) -> &'static str {
+    match target {
+        AstImplementationTarget::All => "all types",
+        AstImplementationTarget::Struct => "structs",
+        AstImplementationTarget::Enum => "enums",
+    }
+}
+
+fn all_tags(ast: &Ast) -> impl Iterator<Item = (&[AstTag], AstImplementationTarget)> {
+    ast.structs
+        .iter()
+        .map(|s| (s.tags.as_slice(), AstImplementationTarget::Struct))
+        .chain(
+            ast.enums
+                .iter()
+                .map(|e| (e.tags.as_slice(), AstImplementationTarget::Enum)),
+        )
+}
+
+fn is_tagged(ast: &Ast, name: &str, target: AstImplementationTarget) -> bool {
+    all_tags(ast).any(|(tags, t)| target.is_valid(t) && tags.iter().any(|(n, _)| n == name))
+}
+
+fn lint_implementations(ast: &Ast, options: &Options, warnings: &mut Vec<String>) {
+    for (index, implementation) in ast.implementations.iter().enumerate() {
+        let name = &implementation.name;
+        let target = target_name(implementation.target);
+        if options.forward.as_ref() != Some(name) && !is_tagged(ast, name, implementation.target) {
+            warnings.push(format!(
+                "Trait `{}` for {} is never applied to any type",
+                name, target
+            ));
+        }
+        let shadowed = ast.implementations[..index].iter().any(|i| {
+            &i.name == name && i.where_rules.is_empty() && i.target == implementation.target
+        });
+        if shadowed {
+            warnings.push(format!(
+                "Trait `{}` for {} can never match because earlier implementation has no where rules",
+                name, target
+            ));
+        }
+    }
+}
+
+fn lint_externs(ast: &Ast, warnings: &mut Vec<String>) {
+    for external in &ast.externs {
+        for (name, _) in &external.implementations {
+            if !is_tagged(ast, name, AstImplementationTarget::All) {
+                warnings.push(format!(
+                    "Trait `{}` of external types `{}` is never applied to any struct or enum",
+                    name,
+                    external.types.join(", ")
+                ));
+            }
+        }
+    }
+}
+
+fn lint_where_rules(
+    rules: &[AstWhereRule],
+    known: &mut Vec<String>,
+    owner: &str,
+    ast: &Ast,
+    warnings: &mut Vec<String>,
+) {
+    for rule in rules {
+        match rule {
+            AstWhereRule::Exists(variable) => {
+                if !known.contains(variable) {
+                    warnings.push(format!(
+                        "Where rule `${} exists` in {} can never match because variable is never supplied",
+                        variable, owner
+                    ));
+                    known.push(variable.to_owned());
+                }
+            }
+            AstWhereRule::Is(rule) => {
+                if !known.contains(&rule.variable) {
+                    warnings.push(format!(
+                        "Where rule `${} is '{}'` in {} can never match because variable is never supplied",
+                        rule.variable, rule.value, owner
+                    ));
+                }
+            }
+            AstWhereRule::Impl(rule) => {
+                if let AstIn::Variable(variable) = &rule.container {
+                    lint_variable(variable, known, owner, warnings);
+                }
+                for name in &rule.implements {
+                    if !ast.implementations.iter().any(|i| &i.name == name)
+                        && !ast
+                            .externs
+                            .iter()
+                            .any(|e| e.implementations.iter().any(|(n, _)| n == name))
+                    {
+                        warnings.push(format!(
+                            "Where rule in {} can never match because trait `{}` does not exist",
+                            owner, name
+                        ));
+                    }
+                }
+            }
+            AstWhereRule::None => {}
+        }
+    }
+}
+
+fn field_tag_parameters(ast: &Ast) -> Vec<&str> {
+    ast.structs
+        .iter()
+        .flat_map(|s| s.fields.iter().map(|f| &f.tags))
+        .chain(
+            ast.enums
+                .iter()
+                .flat_map(|e| e.fields.iter().map(|f| &f.tags)),
+        )
+        .flat_map(|tags| tags.iter().flat_map(|(_, params)| params.keys()))
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+}
+
+fn lint_variable(variable: &str, known: &[String], owner: &str, warnings: &mut Vec<String>) {
+    if !known.iter().any(|k| k == variable) {
+        warnings.push(format!(
+            "Variable `{}` used in {} is never supplied",
+            variable, owner
+        ));
+    }
+}
+
+fn lint_code(code: &AstCode, known: &[String], owner: &str, ast: &Ast, warnings: &mut Vec<String>) {
+    for chunk in &code.0 {
+        match chunk {
+            AstCodeChunk::Variable(variable) => {
+                lint_variable(&variable.name, known, owner, warnings);
+                for filter in &variable.filters {
+                    if apply_filter(filter, "").is_err() {
+                        warnings.push(format!(
+                            "Filter `{}` used in {} does not exist",
+                            filter, owner
+                        ));
+                    }
+                }
+            }
+            AstCodeChunk::For(for_) => {
+                let mut known = known.to_vec();
+                if let AstIn::Variable(variable) = &for_.container {
+                    lint_variable(variable, &known, owner, warnings);
+                }
+                let mut suffixes = vec!["inherited", "resolved", "tags"];
+                if let AstIn::Fields = &for_.container {
+                    suffixes.extend(field_tag_parameters(ast));
+                }
+                for variable in &for_.variables {
+                    known.push(variable.to_owned());
+                    for suffix in &suffixes {
+                        known.push(format!("{}_{}", variable, suffix));
+                    }
+                }
+                lint_where_rules(&for_.where_rules, &mut known, owner, ast, warnings);
+                lint_code(&for_.code, &known, owner, ast, warnings);
+            }
+            AstCodeChunk::Content(_) | AstCodeChunk::None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn warnings(content: &str) -> Vec<String> {
+        let ast = parse(content).unwrap();
+        lint(&ast, &Options::default(), &[], |path| {
+            Err(format!("Unexpected import `{}`", path))
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn lint_accepts_clean_template() {
+        let content = r#"
+impl struct Display(name?)
+```
+%{ for $field in fields where $field impl Display
+```
+%{ $field | snake }%
+```
+}% %{ $TYPENAME }%
+```
+
+extern 'int' {
+  impl Display
+  ```
+  %{ $TYPENAME }%
+  ```
+}
+
+@Display
+struct Foo {
+  a: 'int'
+}
+"#;
+        assert!(warnings(content).is_empty());
+    }
+
+    #[test]
+    fn lint_reports_never_applied_implementation() {
+        let content = r#"
+impl enum Display
+```
+```
+"#;
+        assert_eq!(
+            warnings(content),
+            ["Trait `Display` for enums is never applied to any type"]
+        );
+    }
+
+    #[test]
+    fn lint_reports_shadowed_implementation() {
+        let content = r#"
+impl struct Display
+```
+```
+
+impl struct Display
+```
+```
+
+@Display
+struct Foo {}
+"#;
+        assert_eq!(
+            warnings(content),
+            ["Trait `Display` for structs can never match because earlier implementation has no where rules"]
+        );
+    }
+
+    #[test]
+    fn lint_does_not_shadow_targeted_implementation() {
+        let content = r#"
+impl Display
+```
+```
+
+impl struct Display
+```
+```
+
+@Display
+struct Foo {}
+
+@Display
+enum Bar { A }
+"#;
+        assert!(warnings(content).is_empty());
+    }
+
+    #[test]
+    fn lint_reports_unused_extern_implementation() {
+        let content = r#"
+impl struct Display
+```
+```
+
+extern 'int' {
+  impl Display
+  ```
+  ```
+}
+"#;
+        assert_eq!(
+            warnings(content),
+            [
+                "Trait `Display` for structs is never applied to any type",
+                "Trait `Display` of external types `int` is never applied to any struct or enum",
+            ]
+        );
+    }
+
+    #[test]
+    fn lint_reports_never_supplied_variable() {
+        let content = r#"
+impl struct Display
+```
+%{ $name }%
+```
+
+@Display
+struct Foo {}
+"#;
+        assert_eq!(
+            warnings(content),
+            ["Variable `name` used in trait `Display` for structs is never supplied"]
+        );
+    }
+
+    #[test]
+    fn lint_reports_impossible_where_rules() {
+        let content = r#"
+impl struct Display where $name is 'foo'
+```
+```
+
+impl struct Clone where $TYPENAME impl Missing
+```
+```
+
+@Display
+@Clone
+struct Foo {}
+"#;
+        assert_eq!(
+            warnings(content),
+            [
+                "Where rule `$name is 'foo'` in trait `Display` for structs can never match because variable is never supplied",
+                "Where rule in trait `Clone` for structs can never match because trait `Missing` does not exist",
+            ]
+        );
+    }
+
+    #[test]
+    fn lint_reports_unknown_filter() {
+        let content = r#"
+impl struct Display
+```
+%{ $TYPENAME | title }%
+```
+
+@Display
+struct Foo {}
+"#;
+        assert_eq!(
+            warnings(content),
+            ["Filter `title` used in trait `Display` for structs does not exist"]
+        );
+    }
+}
@@ -1,16 +1,20 @@
-use crate::ast::*;
+use crate::{ast::*, stdlib::STD_PREFIX};
 use pest::{iterators::Pair, Parser};
-use std::collections::HashMap;
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+};
 
 pub fn parse(content: &str) -> Result<Ast, String> {
     let program = match AstParser::parse(Rule::program, content) {
         Ok(mut pairs) => pairs.next().unwrap(),
-        Err(error) => return Err(format!("{:#?}", error)),
+        Err(error) => return Err(format!("{}", error)),
     };
     let mut ast = Ast::default();
     for pair in program.into_inner() {
         match pair.as_rule() {
             Rule::import_elm => ast.imports.push(parse_import(pair)),
+            Rule::alias_elm => ast.aliases.push(parse_alias(pair)),
             Rule::inject_elm => ast.injects.push(parse_inject(pair)),
             Rule::replace_elm => ast.replacements.push(parse_replace(pair)),
             Rule::extern_elm => ast.externs.push(parse_extern(pair)),
@@ -24,12 +28,120 @@ pub fn parse(content: &str) -> Result<Ast, String> {
     Ok(ast)
 }
 
+#[cfg(feature = "serde")]
+pub fn parse_model(content: &str) -> Result<Ast, String> {
+    serde_json::from_str(content).map_err(|error| format!("{}", error))
+}
+
+pub fn resolve_imports<F>(ast: &Ast, on_import: &mut F) -> Result<Ast, String>
+where
+    F: FnMut(&str) -> Result<String, String>,
+{
+    let mut result = ast.clone();
+    let mut imported = vec![];
+    for path in &ast.imports {
+        let path = import_path(Path::new(""), path);
+        resolve_import(&path, on_import, &mut vec![], &mut imported, &mut result)?;
+    }
+    Ok(result)
+}
+
+fn import_path(base: &Path, path: &str) -> String {
+    if path.starts_with(STD_PREFIX) {
+        return path.to_owned();
+    }
+    let mut result = PathBuf::new();
+    for component in base.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match result.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    result.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => result.push(".."),
+            },
+            component => result.push(component),
+        }
+    }
+    result.to_string_lossy().into_owned()
+}
+
+fn resolve_import<F>(
+    path: &str,
+    on_import: &mut F,
+    stack: &mut Vec<String>,
+    imported: &mut Vec<String>,
+    result: &mut Ast,
+) -> Result<(), String>
+where
+    F: FnMut(&str) -> Result<String, String>,
+{
+    if stack.iter().any(|p| p == path) {
+        stack.push(path.to_owned());
+        return Err(format!("Cyclic imports: {}", stack.join(" -> ")));
+    }
+    if imported.iter().any(|p| p == path) {
+        return Ok(());
+    }
+    imported.push(path.to_owned());
+    let content = on_import(path)?;
+    let ast = parse_imported(path, &content).map_err(|error| format!("{}: {}", path, error))?;
+    stack.push(path.to_owned());
+    let base = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    for import in &ast.imports {
+        let import = import_path(base, import);
+        resolve_import(&import, on_import, stack, imported, result)?;
+    }
+    stack.pop();
+    result.merge_with(&ast);
+    Ok(())
+}
+
+pub fn parse_imported(path: &str, content: &str) -> Result<Ast, String> {
+    if path.ends_with(".json") {
+        #[cfg(feature = "serde")]
+        return crate::json_schema::import_json_schema(content);
+        #[cfg(not(feature = "serde"))]
+        return Err("Importing JSON Schema requires `serde` feature".to_owned());
+    }
+    if path.ends_with(".proto") {
+        return crate::proto::import_proto(content);
+    }
+    if path.ends_with(".rs") {
+        #[cfg(feature = "rust")]
+        return crate::rust::import_rust(content);
+        #[cfg(not(feature = "rust"))]
+        return Err("Importing Rust source requires `rust` feature".to_owned());
+    }
+    parse(content)
+}
+
 fn parse_import(pair: Pair<Rule>) -> String {
     parse_string(pair.into_inner().next().unwrap())
 }
 
-fn parse_inject(pair: Pair<Rule>) -> AstCode {
-    parse_code(pair.into_inner().next().unwrap())
+fn parse_alias(pair: Pair<Rule>) -> AstAlias {
+    let mut pairs = pair.into_inner();
+    let name = parse_identifier(pairs.next().unwrap());
+    let type_ = parse_type(pairs.next().unwrap());
+    AstAlias { name, type_ }
+}
+
+fn parse_inject(pair: Pair<Rule>) -> AstInject {
+    let mut result = AstInject::default();
+    for pair in pair.into_inner() {
+        match pair.as_rule() {
+            Rule::into => result.output = Some(parse_into(pair)),
+            Rule::code => result.code = parse_code(pair),
+            _ => panic!("{:?}", pair.as_rule()),
+        }
+    }
+    result
+}
+
+fn parse_into(pair: Pair<Rule>) -> String {
+    parse_string(pair.into_inner().next().unwrap())
 }
 
 fn parse_replace(pair: Pair<Rule>) -> AstReplace {
@@ -72,6 +184,9 @@ fn parse_struct(pair: Pair<Rule>) -> AstStruct {
         match pair.as_rule() {
             Rule::tags => result.tags = parse_tags(pair),
             Rule::identifier => result.name = parse_identifier(pair),
+            Rule::struct_base => {
+                result.base = Some(parse_identifier(pair.into_inner().next().unwrap()))
+            }
             Rule::fields => result.fields = parse_struct_fields(pair),
             _ => panic!("{:?}", pair.as_rule()),
         }
@@ -79,17 +194,24 @@ fn parse_struct(pair: Pair<Rule>) -> AstStruct {
     result
 }
 
-fn parse_struct_fields(pair: Pair<Rule>) -> Vec<(String, AstType)> {
+fn parse_struct_fields(pair: Pair<Rule>) -> Vec<AstField> {
     pair.into_inner()
         .map(parse_struct_field)
         .collect::<Vec<_>>()
 }
 
-fn parse_struct_field(pair: Pair<Rule>) -> (String, AstType) {
+fn parse_struct_field(pair: Pair<Rule>) -> AstField {
     let mut pairs = pair.into_inner();
-    let identifier = parse_identifier(pairs.next().unwrap());
+    let tags = parse_tags(pairs.next().unwrap());
+    let name = parse_identifier(pairs.next().unwrap());
     let type_ = parse_type(pairs.next().unwrap());
-    (identifier, type_)
+    let default = pairs.next().map(parse_string);
+    AstField {
+        tags,
+        name,
+        type_,
+        default,
+    }
 }
 
 fn parse_enum(pair: Pair<Rule>) -> AstEnum {
@@ -105,8 +227,15 @@ fn parse_enum(pair: Pair<Rule>) -> AstEnum {
     result
 }
 
-fn parse_enum_fields(pair: Pair<Rule>) -> Vec<String> {
-    pair.into_inner().map(parse_identifier).collect::<Vec<_>>()
+fn parse_enum_fields(pair: Pair<Rule>) -> Vec<AstEnumField> {
+    pair.into_inner().map(parse_enum_field).collect::<Vec<_>>()
+}
+
+fn parse_enum_field(pair: Pair<Rule>) -> AstEnumField {
+    let mut pairs = pair.into_inner();
+    let tags = parse_tags(pairs.next().unwrap());
+    let name = parse_identifier(pairs.next().unwrap());
+    AstEnumField { tags, name }
 }
 
 fn parse_implementation(pair: Pair<Rule>) -> AstImplementation {
@@ -115,6 +244,11 @@ fn parse_implementation(pair: Pair<Rule>) -> AstImplementation {
         match pair.as_rule() {
             Rule::impl_target => result.target = parse_implementation_target(pair),
             Rule::identifier => result.name = parse_identifier(pair),
+            Rule::impl_params => result.parameters = Some(parse_implementation_parameters(pair)),
+            Rule::impl_requires => {
+                result.requires = pair.into_inner().map(parse_identifier).collect::<Vec<_>>()
+            }
+            Rule::into => result.output = Some(parse_into(pair)),
             Rule::where_rules => result.where_rules = parse_where_rules(pair),
             Rule::code => result.code = parse_code(pair),
             _ => panic!("{:?}", pair.as_rule()),
@@ -123,6 +257,31 @@ fn parse_implementation(pair: Pair<Rule>) -> AstImplementation {
     result
 }
 
+fn parse_implementation_parameters(pair: Pair<Rule>) -> Vec<AstImplementationParameter> {
+    pair.into_inner()
+        .map(parse_implementation_parameter)
+        .collect::<Vec<_>>()
+}
+
+fn parse_implementation_parameter(pair: Pair<Rule>) -> AstImplementationParameter {
+    let mut result = AstImplementationParameter {
+        required: true,
+        ..Default::default()
+    };
+    for pair in pair.into_inner() {
+        match pair.as_rule() {
+            Rule::identifier => result.name = parse_identifier(pair),
+            Rule::impl_param_optional => result.required = false,
+            Rule::value => {
+                result.required = false;
+                result.default = Some(parse_value(pair));
+            }
+            _ => panic!("{:?}", pair.as_rule()),
+        }
+    }
+    result
+}
+
 fn parse_implementation_target(pair: Pair<Rule>) -> AstImplementationTarget {
     let pair = pair.into_inner().next().unwrap();
     match pair.as_rule() {
@@ -141,36 +300,64 @@ fn parse_type(pair: Pair<Rule>) -> AstType {
     }
 }
 
-fn parse_tags(pair: Pair<Rule>) -> Vec<(String, HashMap<String, String>)> {
+fn parse_tags(pair: Pair<Rule>) -> Vec<AstTag> {
     pair.into_inner().map(parse_tag).collect::<Vec<_>>()
 }
 
-fn parse_tag(pair: Pair<Rule>) -> (String, HashMap<String, String>) {
+fn parse_tag(pair: Pair<Rule>) -> AstTag {
     let mut pairs = pair.into_inner();
     let identifier = parse_identifier(pairs.next().unwrap());
     let parameters = if let Some(pair) = pairs.next() {
         pair.into_inner()
             .map(parse_tag_parameter)
-            .collect::<HashMap<_, _>>()
+            .collect::<BTreeMap<_, _>>()
     } else {
         Default::default()
     };
     (identifier, parameters)
 }
 
-fn parse_tag_parameter(pair: Pair<Rule>) -> (String, String) {
+fn parse_tag_parameter(pair: Pair<Rule>) -> (String, AstValue) {
     let mut pairs = pair.into_inner();
     let identifier = parse_identifier(pairs.next().unwrap());
     let value = if let Some(pair) = pairs.next() {
-        parse_string(pair)
+        parse_value(pair)
     } else {
         Default::default()
     };
     (identifier, value)
 }
 
-fn parse_code(pair: Pair<Rule>) -> AstCode {
+fn parse_value(pair: Pair<Rule>) -> AstValue {
     let pair = pair.into_inner().next().unwrap();
+    match pair.as_rule() {
+        Rule::string => AstValue::String(parse_string(pair)),
+        Rule::number => AstValue::Number(pair.as_str().to_owned()),
+        Rule::boolean => AstValue::Bool(pair.as_str() == "true"),
+        Rule::list => AstValue::List(pair.into_inner().map(parse_value).collect::<Vec<_>>()),
+        Rule::map => AstValue::Map(
+            pair.into_inner()
+                .map(parse_tag_parameter)
+                .collect::<Vec<_>>(),
+        ),
+        _ => panic!("{:?}", pair.as_rule()),
+    }
+}
+
+pub fn parse_template(content: &str) -> Result<AstCode, String> {
+    match AstParser::parse(Rule::template, content) {
+        Ok(mut pairs) => Ok(parse_code_inner(
+            pairs.next().unwrap().into_inner().next().unwrap(),
+        )),
+        Err(error) => Err(format!("{}", error)),
+    }
+}
+
+fn parse_code(pair: Pair<Rule>) -> AstCode {
+    parse_code_inner(pair.into_inner().next().unwrap())
+}
+
+fn parse_code_inner(pair: Pair<Rule>) -> AstCode {
     let mut code = AstCode::default();
     for pair in pair.into_inner() {
         match pair.as_rule() {
@@ -180,8 +367,9 @@ fn parse_code(pair: Pair<Rule>) -> AstCode {
             Rule::code_op => {
                 let pair = pair.into_inner().next().unwrap();
                 match pair.as_rule() {
-                    Rule::variable => {
-                        code.0.push(AstCodeChunk::Variable(parse_variable(pair)));
+                    Rule::code_op_variable => {
+                        code.0
+                            .push(AstCodeChunk::Variable(parse_code_variable(pair)));
                     }
                     Rule::code_op_for => {
                         code.0.push(AstCodeChunk::For(parse_code_for(pair)));
@@ -195,6 +383,15 @@ fn parse_code(pair: Pair<Rule>) -> AstCode {
     code
 }
 
+fn parse_code_variable(pair: Pair<Rule>) -> AstCodeVariable {
+    let mut pairs = pair.into_inner();
+    let name = parse_variable(pairs.next().unwrap());
+    let filters = pairs
+        .map(|pair| parse_identifier(pair.into_inner().next().unwrap()))
+        .collect::<Vec<_>>();
+    AstCodeVariable { name, filters }
+}
+
 fn parse_code_for(pair: Pair<Rule>) -> AstCodeFor {
     let mut result = AstCodeFor::default();
     for pair in pair.into_inner() {
@@ -1,6 +1,15 @@
-use crate::ast::*;
+use crate::{
+    ast::*,
+    parser::{parse_template, resolve_imports},
+};
 use regex::{Captures, Regex};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+type Variables = HashMap<String, AstValue>;
+
+pub type Outputs = HashMap<String, String>;
+
+pub const DEFAULT_OUTPUT: &str = "";
 
 enum Context {
     None,
@@ -8,82 +17,604 @@ enum Context {
     Enum(String),
 }
 
+impl std::fmt::Display for Context {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Context::None => Ok(()),
+            Context::Struct(name) => write!(f, "struct `{}`", name),
+            Context::Enum(name) => write!(f, "enum `{}`", name),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Iterable {
+    value: AstValue,
+    companions: Vec<(String, AstValue)>,
+}
+
+impl Iterable {
+    fn new<V: Into<AstValue>>(value: V) -> Self {
+        Self {
+            value: value.into(),
+            companions: vec![],
+        }
+    }
+
+    fn with<V: Into<AstValue>>(mut self, suffix: &str, value: V) -> Self {
+        self.companions.push((suffix.to_owned(), value.into()));
+        self
+    }
+
+    fn with_tags(mut self, tags: &[AstTag]) -> Self {
+        let names = tags
+            .iter()
+            .map(|(name, _)| AstValue::from(name.as_str()))
+            .collect::<Vec<_>>();
+        self = self.with("tags", AstValue::List(names));
+        for (_, params) in tags {
+            for (key, value) in params {
+                self = self.with(key, value.clone());
+            }
+        }
+        self
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TypeOrder {
+    #[default]
+    Declaration,
+    Dependency,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum EmitOrder {
+    #[default]
+    Type,
+    Behaviour,
+    Explicit(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub separator: String,
+    pub type_order: TypeOrder,
+    pub emit_order: EmitOrder,
+    pub forward: Option<String>,
+    pub per_type: Option<String>,
+    pub max_errors: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            separator: "\n".to_owned(),
+            type_order: Default::default(),
+            emit_order: Default::default(),
+            forward: None,
+            per_type: None,
+            max_errors: 20,
+        }
+    }
+}
+
 pub fn process<F>(
     ast: &Ast,
-    separator: &str,
+    options: &Options,
     variables: HashMap<String, String>,
-    _on_import: F,
-) -> Result<String, String>
+    mut on_import: F,
+) -> Result<Outputs, String>
 where
     F: FnMut(&str) -> Result<String, String>,
 {
+    let ast = &resolve_imports(ast, &mut on_import)?;
+    let separator = options.separator.as_str();
+    let variables = variables
+        .into_iter()
+        .map(|(k, v)| (k, AstValue::String(v)))
+        .collect::<HashMap<_, _>>();
     let impls = get_impl_targets(ast);
-    validate_type_impls(ast, &impls)?;
-    let mut output = String::default();
-    for code in &ast.injects {
-        process_code(&Context::None, code, ast, &variables, &mut output)?;
+    let mut errors = vec![];
+    validate_aliases(ast, &mut errors);
+    validate_struct_bases(ast, &mut errors);
+    validate_type_impls(ast, &impls, &mut errors);
+    validate_forward(ast, options, &mut errors);
+    if !errors.is_empty() {
+        return Err(join_errors(errors, options.max_errors));
+    }
+    let mut outputs = Outputs::default();
+    outputs.insert(DEFAULT_OUTPUT.to_owned(), String::default());
+    for inject in &ast.injects {
+        let output = get_output(&mut outputs, &inject.output);
+        process_code(
+            &Context::None,
+            &inject.code,
+            ast,
+            &variables,
+            output,
+            &mut errors,
+        );
         output.push_str(separator);
     }
-    for external in &ast.externs {
-        process_extern(external, ast, separator, &mut output)?;
+    let preamble = outputs[DEFAULT_OUTPUT].clone();
+    let forward = options.forward.as_ref();
+    let forwarded = match forward {
+        Some(forward) => forwarded_structs(ast, forward),
+        None => vec![],
+    };
+    let structs = match options.type_order {
+        TypeOrder::Declaration => ast.structs.iter().collect::<Vec<_>>(),
+        TypeOrder::Dependency => order_structs_by_dependencies(ast, &forwarded)?,
+    };
+    let skipped = match forward {
+        Some(forward) => forward_behaviours(forward, ast),
+        None => vec![],
+    };
+    if let Some(forward) = forward {
+        for enum_ in &ast.enums {
+            process_forward(
+                &Context::Enum(enum_.name.to_owned()),
+                forward,
+                &enum_.tags,
+                ast,
+                separator,
+                &mut outputs,
+                &mut errors,
+            );
+        }
+        for struct_ in &structs {
+            process_forward(
+                &Context::Struct(struct_.name.to_owned()),
+                forward,
+                &struct_.tags,
+                ast,
+                separator,
+                &mut outputs,
+                &mut errors,
+            );
+        }
     }
-    for enum_ in &ast.enums {
-        process_enum(enum_, ast, separator, &mut output)?;
+    let mut type_outputs = HashMap::new();
+    if let Some(per_type) = &options.per_type {
+        let template = parse_template(per_type)?;
+        for enum_ in &ast.enums {
+            let mut name = String::new();
+            let context = Context::Enum(enum_.name.to_owned());
+            process_code(
+                &context,
+                &template,
+                ast,
+                &enum_variables(enum_),
+                &mut name,
+                &mut errors,
+            );
+            add_type_output(&mut type_outputs, &enum_.name, name, &mut errors);
+        }
+        for struct_ in &ast.structs {
+            let mut name = String::new();
+            let context = Context::Struct(struct_.name.to_owned());
+            process_code(
+                &context,
+                &template,
+                ast,
+                &struct_variables(struct_),
+                &mut name,
+                &mut errors,
+            );
+            add_type_output(&mut type_outputs, &struct_.name, name, &mut errors);
+        }
     }
-    for struct_ in &ast.structs {
-        process_struct(struct_, ast, separator, &mut output)?;
+    let behaviours = match &options.emit_order {
+        EmitOrder::Type => vec![None],
+        EmitOrder::Behaviour => get_behaviours_order(ast, &[])?
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>(),
+        EmitOrder::Explicit(names) => get_behaviours_order(ast, names)?
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>(),
+    };
+    for behaviour in &behaviours {
+        let behaviour = behaviour.as_deref();
+        let emitted = |name: &str| {
+            behaviour.map(|b| b == name).unwrap_or(true) && !skipped.iter().any(|s| *s == name)
+        };
+        for external in &ast.externs {
+            process_extern(
+                external,
+                behaviour,
+                ast,
+                separator,
+                &mut outputs,
+                &mut errors,
+            );
+        }
+        for enum_ in &ast.enums {
+            let type_output = type_outputs
+                .get(enum_.name.as_str())
+                .map(|name| (name.as_str(), preamble.as_str()));
+            process_enum(
+                enum_,
+                &emitted,
+                type_output,
+                ast,
+                separator,
+                &mut outputs,
+                &mut errors,
+            );
+        }
+        for struct_ in &structs {
+            let type_output = type_outputs
+                .get(struct_.name.as_str())
+                .map(|name| (name.as_str(), preamble.as_str()));
+            process_struct(
+                struct_,
+                &emitted,
+                type_output,
+                ast,
+                separator,
+                &mut outputs,
+                &mut errors,
+            );
+        }
     }
-    for replace in &ast.replacements {
-        output = process_replacement(replace, &output, ast, &variables);
+    for output in outputs.values_mut() {
+        for replace in &ast.replacements {
+            *output = process_replacement(replace, output, ast, &variables, &mut errors);
+        }
+    }
+    if !errors.is_empty() {
+        return Err(join_errors(errors, options.max_errors));
+    }
+    Ok(outputs)
+}
+
+pub(crate) fn join_errors(errors: Vec<String>, max_errors: usize) -> String {
+    let mut result = vec![];
+    for error in errors {
+        if !result.contains(&error) {
+            result.push(error);
+        }
+    }
+    if max_errors > 0 && result.len() > max_errors {
+        let rest = result.len() - max_errors;
+        result.truncate(max_errors);
+        result.push(format!("... and {} more errors", rest));
     }
-    Ok(output)
+    result.join("\n")
 }
 
-fn get_impl_targets(ast: &Ast) -> Vec<(String, AstImplementationTarget)> {
+fn with_owner(errors: &mut [String], owner: &str) {
+    for error in errors {
+        *error = format!("{} in {}", error, owner);
+    }
+}
+
+fn get_output<'a>(outputs: &'a mut Outputs, name: &Option<String>) -> &'a mut String {
+    outputs
+        .entry(name.as_deref().unwrap_or(DEFAULT_OUTPUT).to_owned())
+        .or_default()
+}
+
+fn get_type_output<'a>(
+    outputs: &'a mut Outputs,
+    name: &Option<String>,
+    type_output: Option<(&str, &str)>,
+) -> &'a mut String {
+    match (name, type_output) {
+        (None, Some((name, preamble))) => outputs
+            .entry(name.to_owned())
+            .or_insert_with(|| preamble.to_owned()),
+        (name, _) => get_output(outputs, name),
+    }
+}
+
+fn get_behaviours_order(ast: &Ast, explicit: &[String]) -> Result<Vec<String>, String> {
+    for name in explicit {
+        if !ast.implementations.iter().any(|i| &i.name == name) {
+            return Err(format!("Trying to order non-existing trait `{}`", name));
+        }
+    }
+    let mut names = vec![];
+    for implementation in &ast.implementations {
+        if !explicit.contains(&implementation.name)
+            && !names.iter().any(|(n, _)| n == &implementation.name)
+        {
+            names.push((implementation.name.to_owned(), ()));
+        }
+    }
+    let mut result = explicit.to_vec();
+    for (name, _) in order_by_requirements(&names, None, "all types", ast)? {
+        result.push(name.to_owned());
+    }
+    Ok(result)
+}
+
+pub(crate) fn get_impl_targets(ast: &Ast) -> Vec<(String, AstImplementationTarget)> {
     ast.implementations
         .iter()
-        .map(|i| (i.name.to_owned(), i.target.clone()))
+        .map(|i| (i.name.to_owned(), i.target))
         .collect::<Vec<_>>()
 }
 
-fn validate_type_impls(
+pub(crate) fn validate_aliases(ast: &Ast, errors: &mut Vec<String>) {
+    'aliases: for alias in &ast.aliases {
+        if ast.structs.iter().any(|s| s.name == alias.name)
+            || ast.enums.iter().any(|e| e.name == alias.name)
+            || ast.aliases.iter().filter(|a| a.name == alias.name).count() > 1
+        {
+            errors.push(format!("Alias `{}` name is already taken", alias.name));
+            continue;
+        }
+        let mut visited = vec![alias.name.as_str()];
+        let mut type_ = &alias.type_;
+        while let AstType::Local(name) = type_ {
+            if ast.structs.iter().any(|s| &s.name == name)
+                || ast.enums.iter().any(|e| &e.name == name)
+            {
+                break;
+            }
+            if visited.contains(&name.as_str()) {
+                errors.push(format!(
+                    "Alias `{}` is cyclic: {}",
+                    alias.name,
+                    visited.join(" -> ")
+                ));
+                continue 'aliases;
+            }
+            match ast.aliases.iter().find(|a| &a.name == name) {
+                Some(found) => {
+                    visited.push(&found.name);
+                    type_ = &found.type_;
+                }
+                None => {
+                    errors.push(format!(
+                        "Alias `{}` points to non-existing type `{}`",
+                        alias.name, name
+                    ));
+                    continue 'aliases;
+                }
+            }
+        }
+    }
+}
+
+fn add_type_output<'a>(
+    type_outputs: &mut HashMap<&'a str, String>,
+    type_: &'a str,
+    name: String,
+    errors: &mut Vec<String>,
+) {
+    if let Some((other, _)) = type_outputs.iter().find(|(_, n)| **n == name) {
+        errors.push(format!(
+            "Types `{}` and `{}` generate the same output file `{}`",
+            other, type_, name
+        ));
+        return;
+    }
+    type_outputs.insert(type_, name);
+}
+
+pub(crate) fn validate_forward(ast: &Ast, options: &Options, errors: &mut Vec<String>) {
+    if let Some(forward) = &options.forward {
+        let exists = ast.implementations.iter().any(|i| &i.name == forward)
+            || ast
+                .externs
+                .iter()
+                .any(|e| e.implementations.iter().any(|(n, _)| n == forward));
+        if !exists {
+            errors.push(format!("Forward behaviour `{}` does not exist", forward));
+        }
+    }
+}
+
+pub(crate) fn validate_struct_bases(ast: &Ast, errors: &mut Vec<String>) {
+    'structs: for struct_ in &ast.structs {
+        let mut visited = vec![struct_.name.as_str()];
+        let mut current = struct_;
+        while let Some(base) = &current.base {
+            if visited.contains(&base.as_str()) {
+                visited.push(base);
+                errors.push(format!(
+                    "Struct `{}` has cyclic inheritance: {}",
+                    struct_.name,
+                    visited.join(" -> ")
+                ));
+                continue 'structs;
+            }
+            match ast.structs.iter().find(|s| &s.name == base) {
+                Some(found) => {
+                    visited.push(&found.name);
+                    current = found;
+                }
+                None => {
+                    errors.push(format!(
+                        "Struct `{}` inherits from non-existing struct `{}`",
+                        current.name, base
+                    ));
+                    continue 'structs;
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn validate_type_impls(
     ast: &Ast,
-    impl_targets: &Vec<(String, AstImplementationTarget)>,
-) -> Result<(), String> {
+    impl_targets: &[(String, AstImplementationTarget)],
+    errors: &mut Vec<String>,
+) {
     for external in &ast.externs {
         for type_ in &external.types {
             for (implementation, _) in &external.implementations {
                 if !impl_targets.iter().any(|(n, _)| implementation == n) {
-                    return Err(format!(
+                    errors.push(format!(
                         "Trying to apply non-existing trait `{}` for external type `{}`",
                         implementation, type_
                     ));
+                    continue;
                 }
+                errors.extend(
+                    validate_requirements(
+                        implementation,
+                        &external.implementations,
+                        None,
+                        &format!("external type `{}`", type_),
+                        ast,
+                    )
+                    .err(),
+                );
             }
         }
     }
-    for struct_ in &ast.structs {
-        for (tag, _) in &struct_.tags {
+    let types = ast
+        .structs
+        .iter()
+        .map(|s| (&s.tags, AstImplementationTarget::Struct, "struct", &s.name))
+        .chain(
+            ast.enums
+                .iter()
+                .map(|e| (&e.tags, AstImplementationTarget::Enum, "enum", &e.name)),
+        );
+    for (tags, target, kind, name) in types {
+        let owner = format!("{} `{}`", kind, name);
+        for (tag, params) in tags {
             if !impl_targets
                 .iter()
-                .any(|(n, t)| tag == n && t.is_valid(AstImplementationTarget::Struct))
+                .any(|(n, t)| tag == n && t.is_valid(target))
             {
-                return Err(format!(
-                    "Trying to apply non-existing or non-struct trait `{}` for struct `{}`",
-                    tag, struct_.name
+                errors.push(format!(
+                    "Trying to apply non-existing or non-{} trait `{}` for {}",
+                    kind, tag, owner
                 ));
+                continue;
             }
+            errors.extend(validate_tag_parameters(tag, params, target, &owner, ast).err());
+            errors.extend(validate_requirements(tag, tags, Some(target), &owner, ast).err());
         }
     }
-    for enum_ in &ast.enums {
-        for (tag, _) in &enum_.tags {
-            if !impl_targets
-                .iter()
-                .any(|(n, t)| tag == n && t.is_valid(AstImplementationTarget::Enum))
-            {
+}
+
+fn get_requirements<'a>(
+    name: &str,
+    target: Option<AstImplementationTarget>,
+    ast: &'a Ast,
+) -> Vec<&'a String> {
+    let mut result = vec![];
+    for implementation in &ast.implementations {
+        if implementation.name == name
+            && target
+                .map(|t| implementation.target.is_valid(t))
+                .unwrap_or(true)
+        {
+            for requirement in &implementation.requires {
+                if !result.contains(&requirement) {
+                    result.push(requirement);
+                }
+            }
+        }
+    }
+    result
+}
+
+fn validate_requirements<T>(
+    name: &str,
+    applied: &[(String, T)],
+    target: Option<AstImplementationTarget>,
+    owner: &str,
+    ast: &Ast,
+) -> Result<(), String> {
+    for requirement in get_requirements(name, target, ast) {
+        if !applied.iter().any(|(n, _)| n == requirement) {
+            return Err(format!(
+                "Trait `{}` requires trait `{}` which is not applied for {}",
+                name, requirement, owner
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn order_by_requirements<'a, T>(
+    items: &'a [(String, T)],
+    target: Option<AstImplementationTarget>,
+    owner: &str,
+    ast: &Ast,
+) -> Result<Vec<&'a (String, T)>, String> {
+    fn visit<'a, T>(
+        index: usize,
+        items: &'a [(String, T)],
+        target: Option<AstImplementationTarget>,
+        owner: &str,
+        ast: &Ast,
+        stack: &mut Vec<usize>,
+        result: &mut Vec<&'a (String, T)>,
+    ) -> Result<(), String> {
+        if result.iter().any(|item| std::ptr::eq(*item, &items[index])) {
+            return Ok(());
+        }
+        if stack.contains(&index) {
+            stack.push(index);
+            return Err(format!(
+                "Traits of {} have cyclic requirements: {}",
+                owner,
+                stack
+                    .iter()
+                    .map(|i| items[*i].0.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            ));
+        }
+        stack.push(index);
+        for requirement in get_requirements(&items[index].0, target, ast) {
+            if let Some(found) = items.iter().position(|(n, _)| n == requirement) {
+                visit(found, items, target, owner, ast, stack, result)?;
+            }
+        }
+        stack.pop();
+        result.push(&items[index]);
+        Ok(())
+    }
+
+    let mut result = Vec::with_capacity(items.len());
+    for index in 0..items.len() {
+        visit(index, items, target, owner, ast, &mut vec![], &mut result)?;
+    }
+    Ok(result)
+}
+
+fn validate_tag_parameters(
+    tag: &str,
+    params: &BTreeMap<String, AstValue>,
+    target: AstImplementationTarget,
+    owner: &str,
+    ast: &Ast,
+) -> Result<(), String> {
+    for implementation in &ast.implementations {
+        if implementation.name != tag || !implementation.target.is_valid(target) {
+            continue;
+        }
+        let declared = match &implementation.parameters {
+            Some(declared) => declared,
+            None => continue,
+        };
+        let mut names = params.keys().collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            if !declared.iter().any(|p| &p.name == name) {
+                return Err(format!(
+                    "Trying to pass undeclared parameter `{}` to trait `{}` for {}",
+                    name, tag, owner
+                ));
+            }
+        }
+        for param in declared {
+            if param.required && !params.contains_key(&param.name) {
                 return Err(format!(
-                    "Trying to apply non-existing or non-enum trait `{}` for enum `{}`",
-                    tag, enum_.name
+                    "Missing required parameter `{}` of trait `{}` for {}",
+                    param.name, tag, owner
                 ));
             }
         }
@@ -91,82 +622,444 @@ fn validate_type_impls(
     Ok(())
 }
 
+fn struct_fields<'a>(ast: &'a Ast, struct_: &'a AstStruct) -> Vec<(&'a AstField, bool)> {
+    let mut result = vec![];
+    let mut current = struct_;
+    for _ in 0..ast.structs.len() {
+        match current
+            .base
+            .as_ref()
+            .and_then(|base| ast.structs.iter().find(|s| &s.name == base))
+        {
+            Some(base) => {
+                result.splice(0..0, base.fields.iter().map(|f| (f, true)));
+                current = base;
+            }
+            None => break,
+        }
+    }
+    result.extend(struct_.fields.iter().map(|f| (f, false)));
+    result
+}
+
+fn order_structs_by_dependencies<'a>(
+    ast: &'a Ast,
+    forwarded: &[&str],
+) -> Result<Vec<&'a AstStruct>, String> {
+    fn visit<'a>(
+        index: usize,
+        ast: &'a Ast,
+        forwarded: &[&str],
+        stack: &mut Vec<usize>,
+        result: &mut Vec<&'a AstStruct>,
+    ) -> Result<(), String> {
+        let struct_ = &ast.structs[index];
+        if result.iter().any(|s| std::ptr::eq(*s, struct_)) {
+            return Ok(());
+        }
+        if stack.contains(&index) {
+            if forwarded.contains(&struct_.name.as_str()) {
+                return Ok(());
+            }
+            stack.push(index);
+            return Err(format!(
+                "Types have cyclic dependencies: {}",
+                stack
+                    .iter()
+                    .map(|i| ast.structs[*i].name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            ));
+        }
+        stack.push(index);
+        let dependencies = struct_
+            .base
+            .iter()
+            .cloned()
+            .chain(struct_.fields.iter().filter_map(|f| {
+                match resolve_type(ast, &f.labeled_type()) {
+                    AstType::Local(name) => Some(name),
+                    _ => None,
+                }
+            }))
+            .collect::<Vec<_>>();
+        for dependency in dependencies {
+            if let Some(found) = ast.structs.iter().position(|s| s.name == dependency) {
+                visit(found, ast, forwarded, stack, result)?;
+            }
+        }
+        stack.pop();
+        result.push(struct_);
+        Ok(())
+    }
+
+    let mut result = Vec::with_capacity(ast.structs.len());
+    for index in 0..ast.structs.len() {
+        visit(index, ast, forwarded, &mut vec![], &mut result)?;
+    }
+    Ok(result)
+}
+
+fn resolve_type(ast: &Ast, type_: &AstType) -> AstType {
+    let mut result = type_;
+    for _ in 0..=ast.aliases.len() {
+        match result {
+            AstType::Local(name) => match ast.aliases.iter().find(|a| &a.name == name) {
+                Some(alias) => result = &alias.type_,
+                None => break,
+            },
+            _ => break,
+        }
+    }
+    result.clone()
+}
+
+fn find_type(ast: &Ast, name: &str) -> AstType {
+    if ast.aliases.iter().any(|a| a.name == name)
+        || ast.structs.iter().any(|s| s.name == name)
+        || ast.enums.iter().any(|e| e.name == name)
+    {
+        AstType::Local(name.to_owned())
+    } else {
+        AstType::Extern(name.to_owned())
+    }
+}
+
+fn type_implements(ast: &Ast, type_: &AstType, implementation: &str) -> bool {
+    match resolve_type(ast, type_) {
+        AstType::Extern(name) => ast.externs.iter().any(|e| {
+            e.types.contains(&name) && e.implementations.iter().any(|(n, _)| n == implementation)
+        }),
+        AstType::Local(name) => {
+            ast.structs
+                .iter()
+                .any(|s| s.name == name && s.tags.iter().any(|(n, _)| n == implementation))
+                || ast
+                    .enums
+                    .iter()
+                    .any(|e| e.name == name && e.tags.iter().any(|(n, _)| n == implementation))
+        }
+        AstType::None => false,
+    }
+}
+
+fn test_where_rules(
+    context: &Context,
+    rules: &[AstWhereRule],
+    ast: &Ast,
+    variables: &Variables,
+) -> Result<bool, String> {
+    for rule in rules {
+        let passed = match rule {
+            AstWhereRule::Exists(variable) => variables.contains_key(variable),
+            AstWhereRule::Is(rule) => match variables.get(&rule.variable) {
+                Some(found) => found.to_string() == rule.value,
+                None => false,
+            },
+            AstWhereRule::Impl(rule) => {
+                let types = match &rule.container {
+                    AstIn::Fields => match context {
+                        Context::Struct(name) => {
+                            let s = ast.structs.iter().find(|s| &s.name == name).unwrap();
+                            struct_fields(ast, s)
+                                .into_iter()
+                                .map(|(f, _)| f.type_.clone())
+                                .collect::<Vec<_>>()
+                        }
+                        Context::Enum(_) => vec![],
+                        Context::None => {
+                            return Err("Trying to test fields of no context".to_owned())
+                        }
+                    },
+                    AstIn::Variable(variable) => match variables.get(variable) {
+                        Some(found) => vec![find_type(ast, &found.to_string())],
+                        None => {
+                            return Err(format!(
+                                "Trying to test non-existing variable `{}`",
+                                variable
+                            ))
+                        }
+                    },
+                    AstIn::None => return Err("There is no container specified to test".to_owned()),
+                };
+                types
+                    .iter()
+                    .all(|t| rule.implements.iter().all(|i| type_implements(ast, t, i)))
+            }
+            AstWhereRule::None => true,
+        };
+        if !passed {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn find_implementation<'a>(
+    context: &Context,
+    name: &str,
+    target: AstImplementationTarget,
+    ast: &'a Ast,
+    variables: &Variables,
+) -> Result<Option<(&'a AstImplementation, Variables)>, String> {
+    let implementations = ast
+        .implementations
+        .iter()
+        .filter(|i| i.target == target)
+        .chain(
+            ast.implementations
+                .iter()
+                .filter(|i| i.target != target && i.target.is_valid(target)),
+        );
+    for implementation in implementations {
+        if implementation.name != name {
+            continue;
+        }
+        let mut variables = variables.clone();
+        if let Some(parameters) = &implementation.parameters {
+            for parameter in parameters {
+                if let Some(default) = &parameter.default {
+                    variables
+                        .entry(parameter.name.to_owned())
+                        .or_insert_with(|| default.clone());
+                }
+            }
+        }
+        if test_where_rules(context, &implementation.where_rules, ast, &variables)? {
+            return Ok(Some((implementation, variables)));
+        }
+    }
+    Ok(None)
+}
+
 fn process_code(
     context: &Context,
     code: &AstCode,
     ast: &Ast,
-    variables: &HashMap<String, String>,
+    variables: &Variables,
     output: &mut String,
-) -> Result<(), String> {
+    errors: &mut Vec<String>,
+) {
     for chunk in &code.0 {
         match chunk {
-            AstCodeChunk::Content(content) => output.push_str(&content),
-            AstCodeChunk::Variable(variable) => {
-                if let Some(found) = variables.get(variable) {
-                    output.push_str(found);
-                } else {
-                    return Err(format!(
-                        "Trying to place non-existing variable `{}`",
-                        variable
-                    ));
+            AstCodeChunk::Content(content) => output.push_str(content),
+            AstCodeChunk::Variable(variable) => match variables.get(&variable.name) {
+                Some(found) => {
+                    let mut value = found.to_string();
+                    for filter in &variable.filters {
+                        match apply_filter(filter, &value) {
+                            Ok(filtered) => value = filtered,
+                            Err(error) => errors.push(error),
+                        }
+                    }
+                    output.push_str(&value);
                 }
+                None => errors.push(format!(
+                    "Trying to place non-existing variable `{}`",
+                    variable.name
+                )),
+            },
+            AstCodeChunk::For(for_) => {
+                process_code_for(context, for_, ast, variables, output, errors)
             }
-            AstCodeChunk::For(for_) => process_code_for(context, for_, ast, variables, output)?,
             AstCodeChunk::None => {}
         }
     }
-    Ok(())
+}
+
+fn split_words(value: &str) -> Vec<String> {
+    let chars = value.chars().collect::<Vec<_>>();
+    let mut result = vec![];
+    let mut word = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                result.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        let prev = if i > 0 { chars.get(i - 1) } else { None };
+        let next = chars.get(i + 1);
+        let boundary = c.is_uppercase()
+            && match prev {
+                Some(p) if p.is_lowercase() || p.is_numeric() => true,
+                Some(p) if p.is_uppercase() => next.map(|n| n.is_lowercase()).unwrap_or(false),
+                _ => false,
+            };
+        if boundary && !word.is_empty() {
+            result.push(std::mem::take(&mut word));
+        }
+        word.push(*c);
+    }
+    if !word.is_empty() {
+        result.push(word);
+    }
+    result
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+pub(crate) fn apply_filter(filter: &str, value: &str) -> Result<String, String> {
+    let words = || split_words(value);
+    match filter {
+        "lower" => Ok(value.to_lowercase()),
+        "upper" => Ok(value.to_uppercase()),
+        "snake" => Ok(words()
+            .iter()
+            .map(|w| w.to_lowercase())
+            .collect::<Vec<_>>()
+            .join("_")),
+        "kebab" => Ok(words()
+            .iter()
+            .map(|w| w.to_lowercase())
+            .collect::<Vec<_>>()
+            .join("-")),
+        "constant" => Ok(words()
+            .iter()
+            .map(|w| w.to_uppercase())
+            .collect::<Vec<_>>()
+            .join("_")),
+        "pascal" => Ok(words().iter().map(|w| capitalize(w)).collect::<String>()),
+        "camel" => Ok(words()
+            .iter()
+            .enumerate()
+            .map(|(i, w)| {
+                if i == 0 {
+                    w.to_lowercase()
+                } else {
+                    capitalize(w)
+                }
+            })
+            .collect::<String>()),
+        _ => Err(format!("Trying to apply non-existing filter `{}`", filter)),
+    }
 }
 
 fn process_code_for(
     context: &Context,
     code: &AstCodeFor,
     ast: &Ast,
-    variables: &HashMap<String, String>,
+    variables: &Variables,
     output: &mut String,
-) -> Result<(), String> {
+    errors: &mut Vec<String>,
+) {
     if code.variables.is_empty() {
-        unreachable!();
-    }
-    let iterables = get_container_iterables(context, &code.container, ast, variables)?;
-    let count = iterables.len() / code.variables.len();
-    for i in 0..count {
-        let start = i * code.variables.len();
-        let end = start + code.variables.len();
-        let values = &iterables[start..end];
+        return errors.push("`for` without loop variables".to_owned());
+    }
+    let rows = match get_container_iterables(
+        context,
+        &code.container,
+        code.variables.len(),
+        ast,
+        variables,
+    ) {
+        Ok(rows) => rows,
+        Err(error) => return errors.push(error),
+    };
+    for row in rows {
         let mut variables = variables.clone();
-        for (name, value) in code.variables.iter().zip(values.iter()) {
-            variables.insert(name.to_owned(), value.to_owned());
+        for (name, iterable) in code.variables.iter().zip(row) {
+            let iterable = match iterable {
+                Some(iterable) => iterable,
+                None => {
+                    variables.remove(name);
+                    continue;
+                }
+            };
+            for (suffix, value) in iterable.companions {
+                variables.insert(format!("{}_{}", name, suffix), value);
+            }
+            variables.insert(name.to_owned(), iterable.value);
+        }
+        match test_where_rules(context, &code.where_rules, ast, &variables) {
+            Ok(true) => process_code(context, &code.code, ast, &variables, output, errors),
+            Ok(false) => {}
+            Err(error) => errors.push(error),
         }
-        process_code(context, &code.code, ast, &variables, output)?;
     }
-    Ok(())
 }
 
 fn get_container_iterables(
     context: &Context,
     container: &AstIn,
+    columns: usize,
     ast: &Ast,
-    variables: &HashMap<String, String>,
-) -> Result<Vec<String>, String> {
+    variables: &Variables,
+) -> Result<Vec<Vec<Option<Iterable>>>, String> {
     match container {
         AstIn::Fields => match context {
             Context::Struct(name) => {
                 let s = ast.structs.iter().find(|s| &s.name == name).unwrap();
-                Ok(s.fields
-                    .iter()
-                    .flat_map(|(n, t)| vec![n.to_owned(), t.to_string()])
+                Ok(struct_fields(ast, s)
+                    .into_iter()
+                    .map(|(f, inherited)| {
+                        vec![
+                            Some(
+                                Iterable::new(f.name.as_str())
+                                    .with("inherited", inherited.to_string())
+                                    .with_tags(&f.tags),
+                            ),
+                            Some(
+                                Iterable::new(f.type_.to_string())
+                                    .with("resolved", resolve_type(ast, &f.type_).to_string()),
+                            ),
+                            f.default.as_deref().map(Iterable::new),
+                        ]
+                    })
                     .collect::<Vec<_>>())
             }
             Context::Enum(name) => {
                 let e = ast.enums.iter().find(|e| &e.name == name).unwrap();
-                Ok(e.fields.clone())
+                Ok(e.fields
+                    .iter()
+                    .map(|f| vec![Some(Iterable::new(f.name.as_str()).with_tags(&f.tags))])
+                    .collect::<Vec<_>>())
             }
             Context::None => Err("Trying to iterate over fields of no context".to_owned()),
         },
         AstIn::Variable(variable) => {
             if let Some(found) = variables.get(variable) {
-                Ok(found.split("|").map(str::to_owned).collect::<Vec<_>>())
+                let values = match found {
+                    AstValue::String(value) => value
+                        .split('|')
+                        .map(|v| Some(Iterable::new(v)))
+                        .collect::<Vec<_>>(),
+                    AstValue::List(items) => items
+                        .iter()
+                        .map(|v| Some(Iterable::new(v.clone())))
+                        .collect::<Vec<_>>(),
+                    AstValue::Map(_) if columns > 2 => {
+                        return Err(format!(
+                            "Trying to iterate over map `{}` with {} variables, expected key and optional value",
+                            variable, columns
+                        ))
+                    }
+                    AstValue::Map(items) => {
+                        return Ok(items
+                            .iter()
+                            .map(|(k, v)| {
+                                vec![
+                                    Some(Iterable::new(k.as_str())),
+                                    Some(Iterable::new(v.clone())),
+                                ]
+                            })
+                            .collect::<Vec<_>>())
+                    }
+                    value => vec![Some(Iterable::new(value.clone()))],
+                };
+                Ok(values
+                    .chunks_exact(columns)
+                    .map(|chunk| chunk.to_vec())
+                    .collect::<Vec<_>>())
             } else {
                 Err(format!(
                     "Trying to iterate over non-existing variable `{}`",
@@ -182,16 +1075,26 @@ fn process_replacement(
     replace: &AstReplace,
     input: &str,
     ast: &Ast,
-    variables: &HashMap<String, String>,
+    variables: &Variables,
+    errors: &mut Vec<String>,
 ) -> String {
-    let pattern = Regex::new(&replace.pattern).expect("Could not parse replacement pattern");
-    println!("* Replace pattern: `{:?}`", pattern);
-    pattern
+    let pattern = match Regex::new(&replace.pattern) {
+        Ok(pattern) => pattern,
+        Err(error) => {
+            errors.push(format!(
+                "Could not parse replacement pattern `{}`: {}",
+                replace.pattern, error
+            ));
+            return input.to_owned();
+        }
+    };
+    let start = errors.len();
+    let result = pattern
         .replace_all(input, |captures: &Captures| {
             let mut variables = variables.clone();
             for i in 0..captures.len() {
                 if let Some(capture) = captures.get(i) {
-                    variables.insert(format!("_{}", i), capture.as_str().to_owned());
+                    variables.insert(format!("_{}", i), capture.as_str().into());
                 }
             }
             let mut output = String::new();
@@ -201,77 +1104,840 @@ fn process_replacement(
                 ast,
                 &variables,
                 &mut output,
-            )
-            .expect("Could not process replacement template code");
+                errors,
+            );
             output
         })
-        .to_owned()
-        .into()
+        .into();
+    with_owner(
+        &mut errors[start..],
+        &format!("replacement `{}`", replace.pattern),
+    );
+    result
 }
 
 fn process_extern(
     external: &AstExtern,
+    behaviour: Option<&str>,
     ast: &Ast,
     separator: &str,
-    output: &mut String,
-) -> Result<(), String> {
+    outputs: &mut Outputs,
+    errors: &mut Vec<String>,
+) {
     for type_ in &external.types {
         let mut variables = HashMap::new();
-        variables.insert("TYPENAME".to_owned(), type_.to_owned());
-        for (_, code) in &external.implementations {
-            process_code(&Context::None, code, ast, &variables, output)?;
+        variables.insert("TYPENAME".to_owned(), type_.as_str().into());
+        let implementations = match order_by_requirements(
+            &external.implementations,
+            None,
+            &format!("external type `{}`", type_),
+            ast,
+        ) {
+            Ok(implementations) => implementations,
+            Err(error) => {
+                errors.push(error);
+                continue;
+            }
+        };
+        for (name, code) in implementations {
+            if behaviour.map(|b| b != name).unwrap_or(false) {
+                continue;
+            }
+            let output = ast
+                .implementations
+                .iter()
+                .find(|i| &i.name == name)
+                .and_then(|i| i.output.as_ref());
+            let output = get_output(outputs, &output.cloned());
+            let start = errors.len();
+            process_code(&Context::None, code, ast, &variables, output, errors);
             output.push_str(separator);
+            with_owner(
+                &mut errors[start..],
+                &format!("trait `{}` for external type `{}`", name, type_),
+            );
         }
     }
-    Ok(())
+}
+
+fn enum_variables(enum_: &AstEnum) -> Variables {
+    let mut variables = HashMap::new();
+    variables.insert("TYPENAME".to_owned(), enum_.name.as_str().into());
+    variables
+}
+
+fn struct_variables(struct_: &AstStruct) -> Variables {
+    let mut variables = HashMap::new();
+    variables.insert("TYPENAME".to_owned(), struct_.name.as_str().into());
+    if let Some(base) = &struct_.base {
+        variables.insert("BASE".to_owned(), base.as_str().into());
+    }
+    variables
+}
+
+fn forward_behaviours<'a>(name: &'a String, ast: &'a Ast) -> Vec<&'a String> {
+    let mut result = vec![name];
+    let mut index = 0;
+    while index < result.len() {
+        for requirement in get_requirements(result[index], None, ast) {
+            if !result.contains(&requirement) {
+                result.push(requirement);
+            }
+        }
+        index += 1;
+    }
+    result
+}
+
+fn forwarded_structs<'a>(ast: &'a Ast, forward: &str) -> Vec<&'a str> {
+    let mut result = vec![];
+    for struct_ in &ast.structs {
+        let context = Context::Struct(struct_.name.to_owned());
+        let mut variables = struct_variables(struct_);
+        if let Some((_, params)) = struct_.tags.iter().find(|(n, _)| n == forward) {
+            for (key, value) in params {
+                variables.insert(key.to_owned(), value.clone());
+            }
+        }
+        let target = AstImplementationTarget::Struct;
+        if let Ok(Some(_)) = find_implementation(&context, forward, target, ast, &variables) {
+            result.push(struct_.name.as_str());
+        }
+    }
+    result
+}
+
+fn process_forward(
+    context: &Context,
+    forward: &String,
+    tags: &[AstTag],
+    ast: &Ast,
+    separator: &str,
+    outputs: &mut Outputs,
+    errors: &mut Vec<String>,
+) {
+    let (target, variables) = match context {
+        Context::Enum(name) => (
+            AstImplementationTarget::Enum,
+            enum_variables(ast.enums.iter().find(|e| &e.name == name).unwrap()),
+        ),
+        Context::Struct(name) => (
+            AstImplementationTarget::Struct,
+            struct_variables(ast.structs.iter().find(|s| &s.name == name).unwrap()),
+        ),
+        Context::None => return,
+    };
+    let owner = context.to_string();
+    let names = forward_behaviours(forward, ast);
+    let mut items = tags
+        .iter()
+        .filter(|(n, _)| names.contains(&n))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !items.iter().any(|(n, _)| n == forward) {
+        items.push((forward.to_owned(), BTreeMap::new()));
+        let params = BTreeMap::new();
+        errors.extend(validate_tag_parameters(forward, &params, target, &owner, ast).err());
+    }
+    let start = errors.len();
+    errors.extend(validate_requirements(forward, &items, Some(target), &owner, ast).err());
+    let items = match order_by_requirements(&items, Some(target), &owner, ast) {
+        Ok(items) if errors.len() == start => items,
+        Ok(_) => return,
+        Err(error) => return errors.push(error),
+    };
+    for (name, params) in items {
+        let mut variables = variables.clone();
+        for (key, value) in params {
+            variables.insert(key.to_owned(), value.clone());
+        }
+        let start = errors.len();
+        match find_implementation(context, name, target, ast, &variables) {
+            Ok(Some((trait_, variables))) => {
+                let output = get_output(outputs, &trait_.output);
+                process_code(context, &trait_.code, ast, &variables, output, errors);
+                output.push_str(separator);
+            }
+            Ok(None) => {}
+            Err(error) => errors.push(error),
+        }
+        with_owner(
+            &mut errors[start..],
+            &format!("trait `{}` for {}", name, context),
+        );
+    }
 }
 
 fn process_enum(
     enum_: &AstEnum,
+    emitted: &dyn Fn(&str) -> bool,
+    type_output: Option<(&str, &str)>,
     ast: &Ast,
     separator: &str,
-    output: &mut String,
-) -> Result<(), String> {
+    outputs: &mut Outputs,
+    errors: &mut Vec<String>,
+) {
     let context = Context::Enum(enum_.name.to_owned());
-    for (name, params) in &enum_.tags {
-        let mut variables = HashMap::new();
-        variables.insert("TYPENAME".to_owned(), enum_.name.to_owned());
+    let tags = match order_by_requirements(
+        &enum_.tags,
+        Some(AstImplementationTarget::Enum),
+        &context.to_string(),
+        ast,
+    ) {
+        Ok(tags) => tags,
+        Err(error) => return errors.push(error),
+    };
+    for (name, params) in tags {
+        if !emitted(name) {
+            continue;
+        }
+        let mut variables = enum_variables(enum_);
         for (key, value) in params {
-            variables.insert(key.to_owned(), value.to_owned());
+            variables.insert(key.to_owned(), value.clone());
         }
-        let trait_ = ast
-            .implementations
-            .iter()
-            .find(|i| &i.name == name && i.target == AstImplementationTarget::Enum)
-            .unwrap();
-        // TODO: where rules validation.
-        process_code(&context, &trait_.code, ast, &variables, output)?;
-        output.push_str(separator);
+        let start = errors.len();
+        match find_implementation(
+            &context,
+            name,
+            AstImplementationTarget::Enum,
+            ast,
+            &variables,
+        ) {
+            Ok(Some((trait_, variables))) => {
+                let output = get_type_output(outputs, &trait_.output, type_output);
+                process_code(&context, &trait_.code, ast, &variables, output, errors);
+                output.push_str(separator);
+            }
+            Ok(None) => {}
+            Err(error) => errors.push(error),
+        }
+        with_owner(
+            &mut errors[start..],
+            &format!("trait `{}` for {}", name, context),
+        );
     }
-    Ok(())
 }
 
 fn process_struct(
     struct_: &AstStruct,
+    emitted: &dyn Fn(&str) -> bool,
+    type_output: Option<(&str, &str)>,
     ast: &Ast,
     separator: &str,
-    output: &mut String,
-) -> Result<(), String> {
+    outputs: &mut Outputs,
+    errors: &mut Vec<String>,
+) {
     let context = Context::Struct(struct_.name.to_owned());
-    for (name, params) in &struct_.tags {
-        let mut variables = HashMap::new();
-        variables.insert("TYPENAME".to_owned(), struct_.name.to_owned());
+    let tags = match order_by_requirements(
+        &struct_.tags,
+        Some(AstImplementationTarget::Struct),
+        &context.to_string(),
+        ast,
+    ) {
+        Ok(tags) => tags,
+        Err(error) => return errors.push(error),
+    };
+    for (name, params) in tags {
+        if !emitted(name) {
+            continue;
+        }
+        let mut variables = struct_variables(struct_);
         for (key, value) in params {
-            variables.insert(key.to_owned(), value.to_owned());
+            variables.insert(key.to_owned(), value.clone());
         }
-        let trait_ = ast
-            .implementations
-            .iter()
-            .find(|i| &i.name == name && i.target == AstImplementationTarget::Struct)
-            .unwrap();
-        // TODO: where rules validation.
-        process_code(&context, &trait_.code, ast, &variables, output)?;
-        output.push_str(separator);
+        let start = errors.len();
+        match find_implementation(
+            &context,
+            name,
+            AstImplementationTarget::Struct,
+            ast,
+            &variables,
+        ) {
+            Ok(Some((trait_, variables))) => {
+                let output = get_type_output(outputs, &trait_.output, type_output);
+                process_code(&context, &trait_.code, ast, &variables, output, errors);
+                output.push_str(separator);
+            }
+            Ok(None) => {}
+            Err(error) => errors.push(error),
+        }
+        with_owner(
+            &mut errors[start..],
+            &format!("trait `{}` for {}", name, context),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn generate_with(content: &str, options: &Options) -> Result<String, String> {
+        let ast = parse(content)?;
+        let mut outputs = process(&ast, options, HashMap::new(), |path| {
+            Err(format!("Unexpected import `{}`", path))
+        })?;
+        Ok(outputs.remove(DEFAULT_OUTPUT).unwrap_or_default())
+    }
+
+    fn generate(content: &str) -> Result<String, String> {
+        generate_with(content, &Options::default())
+    }
+
+    #[test]
+    fn apply_filter_changes_case() {
+        let cases = [
+            ("lower", "HttpServer", "httpserver"),
+            ("upper", "HttpServer", "HTTPSERVER"),
+            ("snake", "HTTPServer2Config", "http_server2_config"),
+            ("kebab", "my_value-name", "my-value-name"),
+            ("constant", "maxRetryCount", "MAX_RETRY_COUNT"),
+            ("pascal", "foo_bar baz", "FooBarBaz"),
+            ("camel", "XMLHttpRequest", "xmlHttpRequest"),
+            ("snake", "", ""),
+        ];
+        for (filter, value, expected) in cases {
+            assert_eq!(apply_filter(filter, value).unwrap(), expected, "{}", filter);
+        }
+    }
+
+    #[test]
+    fn apply_filter_rejects_unknown_filter() {
+        assert_eq!(
+            apply_filter("title", "foo").unwrap_err(),
+            "Trying to apply non-existing filter `title`"
+        );
+    }
+
+    #[test]
+    fn for_clears_missing_field_default() {
+        let content = r#"
+impl struct Fields(default?)
+```
+%{ for $name $type $default in fields where $default exists
+```
+%{ $name }%=%{ $default }%;
+```
+}%
+```
+
+@Fields { default = 'outer' }
+struct Foo {
+  a: 'int' = '1'
+  b: 'int'
+}
+"#;
+        assert_eq!(generate(content).unwrap().trim(), "a=1;");
+    }
+
+    #[test]
+    fn alias_resolves_field_types() {
+        let content = r#"
+impl struct Fields
+```
+%{ for $name $type in fields
+```
+%{ $name }%: %{ $type }% = %{ $type_resolved }%;
+```
+}%
+```
+
+alias Id = 'uint64_t'
+alias Key = Id
+
+@Fields
+struct Foo {
+  a: Key
+}
+"#;
+        assert_eq!(generate(content).unwrap().trim(), "a: Key = uint64_t;");
+    }
+
+    #[test]
+    fn alias_rejects_unknown_type_and_cycles() {
+        let content = r#"
+alias A = Missing
+alias B = C
+alias C = B
+"#;
+        assert_eq!(
+            generate(content).unwrap_err(),
+            "Alias `A` points to non-existing type `Missing`\n\
+             Alias `B` is cyclic: B -> C\n\
+             Alias `C` is cyclic: C -> B"
+        );
+    }
+
+    #[test]
+    fn struct_inherits_base_fields() {
+        let content = r#"
+impl struct Fields
+```
+%{ $TYPENAME }%(%{ $BASE }%):
+%{ for $name in fields
+```
+%{ $name }%=%{ $name_inherited }%
+```
+}%
+```
+
+struct Header {
+  id: 'int'
+}
+
+struct Message : Header {
+  body: 'int'
+}
+
+@Fields
+struct Reply : Message {
+  code: 'int'
+}
+"#;
+        assert_eq!(
+            generate(content).unwrap().trim(),
+            "Reply(Message):\nid=true\nbody=true\ncode=false"
+        );
+    }
+
+    #[test]
+    fn struct_rejects_unknown_base_and_cycles() {
+        let content = r#"
+struct A : Missing {}
+struct B : C {}
+struct C : B {}
+"#;
+        assert_eq!(
+            generate(content).unwrap_err(),
+            "Struct `A` inherits from non-existing struct `Missing`\n\
+             Struct `B` has cyclic inheritance: B -> C -> B\n\
+             Struct `C` has cyclic inheritance: C -> B -> C"
+        );
+    }
+
+    #[test]
+    fn tag_values_are_iterable() {
+        let content = r#"
+impl struct Rust
+```
+%{ for $derive in $derives
+```
+derive(%{ $derive }%)
+```
+}%
+%{ for $key $value in $serde
+```
+serde(%{ $key }%=%{ $value }%)
+```
+}%
+version=%{ $version }% copy=%{ $copy }%
+```
+
+@Rust { derives = ['Debug' 'Clone'] serde = { rename_all = 'camelCase' } version = 2 copy = true }
+struct Point {}
+"#;
+        assert_eq!(
+            generate(content).unwrap().trim(),
+            "derive(Debug)\nderive(Clone)\n\nserde(rename_all=camelCase)\n\nversion=2 copy=true"
+        );
+    }
+
+    #[test]
+    fn tag_map_rejects_too_many_variables() {
+        let content = r#"
+impl struct Rust
+```
+%{ for $a $b $c in $serde
+```
+%{ $a }%
+```
+}%
+```
+
+@Rust { serde = { rename_all = 'camelCase' } }
+struct Point {}
+"#;
+        assert_eq!(
+            generate(content).unwrap_err(),
+            "Trying to iterate over map `serde` with 3 variables, expected key and optional value in trait `Rust` for struct `Point`"
+        );
+    }
+
+    #[test]
+    fn parameters_use_defaults() {
+        let content = r#"
+impl enum Describe(name inherit = 'int' hidden?)
+```
+%{ $name }%: %{ $inherit }%
+```
+
+@Describe { name = 'a' }
+enum A { X }
+
+@Describe { name = 'b' inherit = 'uint8' }
+enum B { X }
+"#;
+        assert_eq!(generate(content).unwrap().trim(), "a: int\n\nb: uint8");
+    }
+
+    #[test]
+    fn parameters_reject_missing_and_undeclared() {
+        let content = r#"
+impl enum Describe(name)
+```
+```
+
+@Describe
+enum A { X }
+
+@Describe { name = 'b' typo = 'c' }
+enum B { X }
+"#;
+        assert_eq!(
+            generate(content).unwrap_err(),
+            "Missing required parameter `name` of trait `Describe` for enum `A`\n\
+             Trying to pass undeclared parameter `typo` to trait `Describe` for enum `B`"
+        );
+    }
+
+    #[test]
+    fn join_errors_dedups_and_caps() {
+        let errors = ["a", "b", "a", "c", "d"].map(String::from).to_vec();
+        assert_eq!(join_errors(errors.clone(), 0), "a\nb\nc\nd");
+        assert_eq!(join_errors(errors.clone(), 4), "a\nb\nc\nd");
+        assert_eq!(join_errors(errors, 2), "a\nb\n... and 2 more errors");
+    }
+
+    #[test]
+    fn process_caps_reported_errors() {
+        let content = r#"
+alias A = Missing
+alias B = Missing
+alias C = Missing
+"#;
+        let options = Options {
+            max_errors: 1,
+            ..Options::default()
+        };
+        assert_eq!(
+            generate_with(content, &options).unwrap_err(),
+            "Alias `A` points to non-existing type `Missing`\n... and 2 more errors"
+        );
+    }
+
+    #[test]
+    fn requires_orders_behaviours() {
+        let content = r#"
+impl struct Serialize requires Display Clone
+```
+serialize
+```
+
+impl struct Display
+```
+display
+```
+
+impl struct Clone requires Display
+```
+clone
+```
+
+@Serialize
+@Clone
+@Display
+struct Foo {}
+"#;
+        assert_eq!(
+            generate(content).unwrap().trim(),
+            "display\n\nclone\n\nserialize"
+        );
+    }
+
+    #[test]
+    fn requires_rejects_missing_behaviour() {
+        let content = r#"
+impl struct Serialize requires Display
+```
+```
+
+impl struct Display
+```
+```
+
+@Serialize
+struct Foo {}
+"#;
+        assert_eq!(
+            generate(content).unwrap_err(),
+            "Trait `Serialize` requires trait `Display` which is not applied for struct `Foo`"
+        );
+    }
+
+    #[test]
+    fn requires_rejects_cycles() {
+        let content = r#"
+impl struct A requires B
+```
+```
+
+impl struct B requires A
+```
+```
+
+@A
+@B
+struct Foo {}
+"#;
+        assert_eq!(
+            generate(content).unwrap_err(),
+            "Traits of struct `Foo` have cyclic requirements: A -> B -> A"
+        );
+    }
+
+    #[test]
+    fn emit_orders_behaviours() {
+        let content = r#"
+impl struct Describe
+```
+describe %{ $TYPENAME }%
+```
+
+impl struct Display
+```
+display %{ $TYPENAME }%
+```
+
+@Display
+@Describe
+struct A {}
+
+@Describe
+@Display
+struct B {}
+"#;
+        let emit = |emit_order| {
+            let options = Options {
+                emit_order,
+                ..Options::default()
+            };
+            generate_with(content, &options)
+                .unwrap()
+                .lines()
+                .filter(|l| !l.is_empty())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        assert_eq!(
+            emit(EmitOrder::Type),
+            "display A, describe A, describe B, display B"
+        );
+        assert_eq!(
+            emit(EmitOrder::Behaviour),
+            "describe A, describe B, display A, display B"
+        );
+        assert_eq!(
+            emit(EmitOrder::Explicit(vec!["Display".to_owned()])),
+            "display A, display B, describe A, describe B"
+        );
+    }
+
+    #[test]
+    fn emit_rejects_unknown_behaviour() {
+        let options = Options {
+            emit_order: EmitOrder::Explicit(vec!["Missing".to_owned()]),
+            ..Options::default()
+        };
+        assert_eq!(
+            generate_with("struct A {}", &options).unwrap_err(),
+            "Trying to order non-existing trait `Missing`"
+        );
+    }
+
+    fn generate_ordered(content: &str, forward: Option<&str>) -> Result<String, String> {
+        let options = Options {
+            type_order: TypeOrder::Dependency,
+            forward: forward.map(str::to_owned),
+            ..Options::default()
+        };
+        generate_with(content, &options).map(|output| {
+            output
+                .lines()
+                .filter(|l| !l.is_empty())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+    }
+
+    #[test]
+    fn dependency_order_emits_dependencies_first() {
+        let content = r#"
+impl struct Define
+```
+define %{ $TYPENAME }%
+```
+
+@Define
+struct Message : Header {
+  body: Body
+}
+
+@Define
+struct Body {}
+
+@Define
+struct Header {}
+"#;
+        assert_eq!(
+            generate_ordered(content, None).unwrap(),
+            "define Header, define Body, define Message"
+        );
+    }
+
+    #[test]
+    fn dependency_order_rejects_cycles() {
+        let content = r#"
+struct A {
+  b: B
+}
+
+struct B {
+  a: A
+}
+"#;
+        assert_eq!(
+            generate_ordered(content, None).unwrap_err(),
+            "Types have cyclic dependencies: A -> B -> A"
+        );
+    }
+
+    #[test]
+    fn forward_allows_cycles_of_forwarded_types() {
+        let content = r#"
+impl struct Forward
+```
+forward %{ $TYPENAME }%
+```
+
+impl struct Define
+```
+define %{ $TYPENAME }%
+```
+
+@Forward
+@Define
+struct A {
+  b: B
+}
+
+@Define
+struct B {
+  a: A
+}
+"#;
+        assert_eq!(
+            generate_ordered(content, Some("Forward")).unwrap(),
+            "forward B, forward A, define B, define A"
+        );
+    }
+
+    #[test]
+    fn forward_rejects_cycles_without_forward_implementation() {
+        let content = r#"
+impl enum Forward
+```
+forward %{ $TYPENAME }%
+```
+
+struct A {
+  b: B
+}
+
+struct B {
+  a: A
+}
+"#;
+        assert_eq!(
+            generate_ordered(content, Some("Forward")).unwrap_err(),
+            "Types have cyclic dependencies: A -> B -> A"
+        );
+    }
+
+    #[test]
+    fn forward_emits_requirements_first() {
+        let content = r#"
+impl struct Forward(name = 'default') requires Include
+```
+forward %{ $name }%
+```
+
+impl struct Include
+```
+include %{ $TYPENAME }%
+```
+
+@Include
+@Forward { name = 'a' }
+struct A {}
+
+@Include
+struct B {}
+"#;
+        assert_eq!(
+            generate_ordered(content, Some("Forward")).unwrap(),
+            "include A, forward a, include B, forward default"
+        );
+    }
+
+    #[test]
+    fn forward_validates_parameters_and_requirements() {
+        let content = r#"
+impl struct Forward(name) requires Include
+```
+```
+
+impl struct Include
+```
+```
+
+struct A {}
+"#;
+        assert_eq!(
+            generate_ordered(content, Some("Forward")).unwrap_err(),
+            "Missing required parameter `name` of trait `Forward` for struct `A`\n\
+             Trait `Forward` requires trait `Include` which is not applied for struct `A`"
+        );
+    }
+
+    #[test]
+    fn for_rejects_missing_loop_variables() {
+        let content = r#"
+impl struct Fields
+```
+%{ for $name in fields
+```
+%{ $name }%
+```
+}%
+```
+
+@Fields
+struct Foo {
+  a: 'int'
+}
+"#;
+        let mut ast = parse(content).unwrap();
+        if let AstCodeChunk::For(for_) = &mut ast.implementations[0].code.0[0] {
+            for_.variables.clear();
+        }
+        let error = process(&ast, &Options::default(), HashMap::new(), |path| {
+            Err(format!("Unexpected import `{}`", path))
+        })
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "`for` without loop variables in trait `Fields` for struct `Foo`"
+        );
     }
-    Ok(())
 }
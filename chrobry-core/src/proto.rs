@@ -0,0 +1,425 @@
+use crate::ast::{
+    Ast, AstEnum, AstEnumField, AstField, AstStruct, AstTag, AstType, AstValue, FIELD_KEY,
+    FIELD_LABEL,
+};
+use pest::{iterators::Pair, Parser};
+use std::collections::{BTreeMap, HashMap};
+
+pub const PROTO_TAG: &str = "Proto";
+pub const TAGS_OPTION: &str = "(chrobry)";
+
+const SCALARS: &[&str] = &[
+    "double", "float", "int32", "int64", "uint32", "uint64", "sint32", "sint64", "fixed32",
+    "fixed64", "sfixed32", "sfixed64", "bool", "string", "bytes",
+];
+
+#[derive(Parser)]
+#[grammar = "proto.pest"]
+struct ProtoParser;
+
+enum Label {
+    None,
+    Repeated,
+    Optional,
+}
+
+struct PendingType {
+    struct_index: usize,
+    field_index: usize,
+    scope: Vec<String>,
+    label: Label,
+    types: Vec<String>,
+}
+
+#[derive(Default)]
+struct Importer {
+    package: String,
+    ast: Ast,
+    declared: HashMap<String, String>,
+    pending: Vec<PendingType>,
+}
+
+pub fn import_proto(content: &str) -> Result<Ast, String> {
+    let proto = match ProtoParser::parse(Rule::proto, content) {
+        Ok(mut pairs) => pairs.next().unwrap(),
+        Err(error) => return Err(format!("{}", error)),
+    };
+    let mut importer = Importer::default();
+    for pair in proto.into_inner() {
+        match pair.as_rule() {
+            Rule::package => {
+                importer.package = pair.into_inner().next().unwrap().as_str().to_owned()
+            }
+            Rule::message => importer.import_message(pair, &[]),
+            Rule::enumeration => importer.import_enum(pair, &[]),
+            Rule::syntax
+            | Rule::import
+            | Rule::option
+            | Rule::service
+            | Rule::extend
+            | Rule::EOI => {}
+            _ => panic!("{:?}", pair.as_rule()),
+        }
+    }
+    Ok(importer.finish())
+}
+
+impl Importer {
+    fn declare(&mut self, scope: &[String], name: &str) -> String {
+        let path = scope
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(name))
+            .collect::<Vec<_>>();
+        let type_name = path.join("_");
+        self.declared.insert(path.join("."), type_name.to_owned());
+        type_name
+    }
+
+    fn import_message(&mut self, pair: Pair<Rule>, scope: &[String]) {
+        let mut pairs = pair.into_inner();
+        let name = pairs.next().unwrap().as_str().to_owned();
+        let type_name = self.declare(scope, &name);
+        let struct_index = self.ast.structs.len();
+        self.ast.structs.push(AstStruct {
+            name: type_name,
+            ..Default::default()
+        });
+        let mut scope = scope.to_vec();
+        scope.push(name);
+        for pair in pairs {
+            match pair.as_rule() {
+                Rule::field => self.import_field(pair, struct_index, &scope, None),
+                Rule::map_field => self.import_field(pair, struct_index, &scope, None),
+                Rule::oneof => {
+                    let mut pairs = pair.into_inner();
+                    let oneof = pairs.next().unwrap().as_str().to_owned();
+                    for pair in pairs.filter(|p| p.as_rule() == Rule::oneof_field) {
+                        self.import_field(pair, struct_index, &scope, Some(&oneof));
+                    }
+                }
+                Rule::message => self.import_message(pair, &scope),
+                Rule::enumeration => self.import_enum(pair, &scope),
+                Rule::option => {
+                    if let Some(tags) = parse_tags_option(pair) {
+                        self.ast.structs[struct_index].tags = tags;
+                    }
+                }
+                Rule::reserved | Rule::extend => {}
+                _ => panic!("{:?}", pair.as_rule()),
+            }
+        }
+    }
+
+    fn import_field(
+        &mut self,
+        pair: Pair<Rule>,
+        struct_index: usize,
+        scope: &[String],
+        oneof: Option<&str>,
+    ) {
+        let mut label = match oneof {
+            Some(_) => Label::Optional,
+            None => Label::None,
+        };
+        let mut types = vec![];
+        let mut name = String::new();
+        let mut params = BTreeMap::new();
+        for pair in pair.into_inner() {
+            match pair.as_rule() {
+                Rule::label => match pair.as_str() {
+                    "repeated" => label = Label::Repeated,
+                    "optional" => label = Label::Optional,
+                    _ => {}
+                },
+                Rule::type_name => types.push(pair.as_str().to_owned()),
+                Rule::identifier => name = pair.as_str().to_owned(),
+                Rule::integer => {
+                    params.insert(
+                        "number".to_owned(),
+                        AstValue::Number(pair.as_str().to_owned()),
+                    );
+                }
+                Rule::field_options => params.extend(parse_field_options(pair)),
+                _ => panic!("{:?}", pair.as_rule()),
+            }
+        }
+        if let Some(oneof) = oneof {
+            params.insert("oneof".to_owned(), AstValue::from(oneof));
+        }
+        let fields = &mut self.ast.structs[struct_index].fields;
+        self.pending.push(PendingType {
+            struct_index,
+            field_index: fields.len(),
+            scope: scope.to_vec(),
+            label,
+            types,
+        });
+        fields.push(AstField {
+            tags: vec![(PROTO_TAG.to_owned(), params)],
+            name,
+            ..Default::default()
+        });
+    }
+
+    fn import_enum(&mut self, pair: Pair<Rule>, scope: &[String]) {
+        let mut pairs = pair.into_inner();
+        let name = pairs.next().unwrap().as_str();
+        let mut result = AstEnum {
+            name: self.declare(scope, name),
+            ..Default::default()
+        };
+        for pair in pairs {
+            match pair.as_rule() {
+                Rule::enum_field => {
+                    let mut pairs = pair.into_inner();
+                    let name = pairs.next().unwrap().as_str().to_owned();
+                    let mut params = BTreeMap::new();
+                    params.insert(
+                        "number".to_owned(),
+                        AstValue::Number(pairs.next().unwrap().as_str().to_owned()),
+                    );
+                    for pair in pairs {
+                        params.extend(parse_field_options(pair));
+                    }
+                    result.fields.push(AstEnumField {
+                        tags: vec![(PROTO_TAG.to_owned(), params)],
+                        name,
+                    });
+                }
+                Rule::option => {
+                    if let Some(tags) = parse_tags_option(pair) {
+                        result.tags = tags;
+                    }
+                }
+                Rule::reserved => {}
+                _ => panic!("{:?}", pair.as_rule()),
+            }
+        }
+        self.ast.enums.push(result);
+    }
+
+    fn resolve(&self, name: &str, scope: &[String]) -> AstType {
+        if SCALARS.contains(&name) {
+            return AstType::Extern(name.to_owned());
+        }
+        let package = format!("{}.", self.package);
+        let found = match name.strip_prefix('.') {
+            Some(name) => self
+                .declared
+                .get(name.strip_prefix(&package).unwrap_or(name)),
+            None => {
+                let relative = name.strip_prefix(&package).unwrap_or(name);
+                (0..=scope.len()).rev().find_map(|i| {
+                    let mut path = scope[..i].to_vec();
+                    path.push(relative.to_owned());
+                    self.declared.get(&path.join("."))
+                })
+            }
+        };
+        match found {
+            Some(found) => AstType::Local(found.to_owned()),
+            None => AstType::Extern(name.to_owned()),
+        }
+    }
+
+    fn finish(mut self) -> Ast {
+        for pending in std::mem::take(&mut self.pending) {
+            let types = pending
+                .types
+                .iter()
+                .map(|name| self.resolve(name, &pending.scope))
+                .collect::<Vec<_>>();
+            let field = &mut self.ast.structs[pending.struct_index].fields[pending.field_index];
+            let params = &mut field.tags[0].1;
+            let (label, type_) = match (&pending.label, types.as_slice()) {
+                (_, [key, value]) => {
+                    params.insert(FIELD_KEY.to_owned(), AstValue::from(key.to_string()));
+                    (Some("map"), value.to_owned())
+                }
+                (Label::Repeated, [type_]) => (Some("repeated"), type_.to_owned()),
+                (Label::Optional, [type_]) => (Some("optional"), type_.to_owned()),
+                (_, [type_]) => (None, type_.to_owned()),
+                _ => (None, AstType::None),
+            };
+            if let Some(label) = label {
+                params.insert(FIELD_LABEL.to_owned(), AstValue::from(label));
+            }
+            field.type_ = type_;
+        }
+        self.ast
+    }
+}
+
+fn parse_tags_option(pair: Pair<Rule>) -> Option<Vec<AstTag>> {
+    let mut pairs = pair.into_inner();
+    if pairs.next().unwrap().as_str() != TAGS_OPTION {
+        return None;
+    }
+    match parse_constant(pairs.next().unwrap()) {
+        AstValue::String(names) => Some(
+            names
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|name| !name.is_empty())
+                .map(|name| (name.to_owned(), BTreeMap::new()))
+                .collect::<Vec<_>>(),
+        ),
+        _ => None,
+    }
+}
+
+fn parse_field_options(pair: Pair<Rule>) -> Vec<(String, AstValue)> {
+    pair.into_inner()
+        .map(|pair| {
+            let mut pairs = pair.into_inner();
+            let name = pairs.next().unwrap().as_str().to_owned();
+            (name, parse_constant(pairs.next().unwrap()))
+        })
+        .collect::<Vec<_>>()
+}
+
+fn parse_constant(pair: Pair<Rule>) -> AstValue {
+    let pair = pair.into_inner().next().unwrap();
+    match pair.as_rule() {
+        Rule::string => AstValue::String(pair.into_inner().next().unwrap().as_str().to_owned()),
+        Rule::scalar => match pair.as_str() {
+            "true" => AstValue::Bool(true),
+            "false" => AstValue::Bool(false),
+            value if value.parse::<f64>().is_ok() => AstValue::Number(value.to_owned()),
+            value => AstValue::String(value.to_owned()),
+        },
+        _ => AstValue::String(pair.as_str().to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field<'a>(ast: &'a Ast, struct_: &str, name: &str) -> &'a AstField {
+        ast.structs
+            .iter()
+            .find(|s| s.name == struct_)
+            .unwrap()
+            .fields
+            .iter()
+            .find(|f| f.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn import_proto_resolves_nested_scopes() {
+        let ast = import_proto(
+            r#"
+            syntax = "proto3";
+            package net;
+
+            message Host {
+              option (chrobry) = "Display Clone";
+              message Port {
+                enum Kind { TCP = 0; UDP = 1 [deprecated = true]; }
+                Kind kind = 1;
+              }
+              Port port = 1;
+              .net.Host.Port.Kind kind = 2;
+              Status status = 3;
+              google.protobuf.Any extra = 4;
+            }
+
+            enum Status { UP = 0; DOWN = 1; }
+            "#,
+        )
+        .unwrap();
+        let structs = ast
+            .structs
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(structs, vec!["Host", "Host_Port"]);
+        let enums = ast
+            .enums
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(enums, vec!["Host_Port_Kind", "Status"]);
+        assert_eq!(
+            ast.structs[0]
+                .tags
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Display", "Clone"]
+        );
+        let types = ast.structs[0]
+            .fields
+            .iter()
+            .map(|f| f.type_.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                AstType::Local("Host_Port".to_owned()),
+                AstType::Local("Host_Port_Kind".to_owned()),
+                AstType::Local("Status".to_owned()),
+                AstType::Extern("google.protobuf.Any".to_owned()),
+            ]
+        );
+        assert_eq!(
+            field(&ast, "Host_Port", "kind").type_,
+            AstType::Local("Host_Port_Kind".to_owned())
+        );
+        let udp = &ast.enums[0].fields[1];
+        assert_eq!(udp.tags[0].1["number"], AstValue::Number("1".to_owned()));
+        assert_eq!(udp.tags[0].1["deprecated"], AstValue::Bool(true));
+    }
+
+    #[test]
+    fn import_proto_labels_fields() {
+        let ast = import_proto(
+            r#"
+            message Tree {
+              string name = 1;
+              repeated Tree children = 2;
+              optional Tree parent = 3;
+              map<string, Tree> named = 4;
+              oneof value {
+                int32 number = 5;
+                Tree other = 6;
+              }
+            }
+            "#,
+        )
+        .unwrap();
+        let fields = ast.structs[0]
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.type_.clone(), f.label()))
+            .collect::<Vec<_>>();
+        let local = || AstType::Local("Tree".to_owned());
+        assert_eq!(
+            fields,
+            vec![
+                ("name", AstType::Extern("string".to_owned()), None),
+                ("children", local(), Some("repeated")),
+                ("parent", local(), Some("optional")),
+                ("named", local(), Some("map")),
+                (
+                    "number",
+                    AstType::Extern("int32".to_owned()),
+                    Some("optional")
+                ),
+                ("other", local(), Some("optional")),
+            ]
+        );
+        let named = field(&ast, "Tree", "named");
+        assert_eq!(named.tags[0].1[FIELD_KEY], AstValue::from("string"));
+        assert_eq!(
+            named.labeled_type(),
+            AstType::Extern("map<string, Tree>".to_owned())
+        );
+        let other = field(&ast, "Tree", "other");
+        assert_eq!(other.tags[0].0, PROTO_TAG);
+        assert_eq!(other.tags[0].1["oneof"], AstValue::from("value"));
+        assert_eq!(other.tags[0].1["number"], AstValue::Number("6".to_owned()));
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+pub const REGION_BEGIN: &str = "chrobry:begin";
+pub const REGION_END: &str = "chrobry:end";
+
+enum Line<'a> {
+    Content(&'a str),
+    Begin(&'a str, String),
+    End(&'a str),
+}
+
+fn parse_line(line: &str) -> Line<'_> {
+    if let Some(index) = line.find(REGION_BEGIN) {
+        let name = line[index + REGION_BEGIN.len()..]
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_owned();
+        Line::Begin(line, name)
+    } else if line.contains(REGION_END) {
+        Line::End(line)
+    } else {
+        Line::Content(line)
+    }
+}
+
+pub fn find_regions(content: &str) -> Result<Vec<(String, String)>, String> {
+    let mut result = vec![];
+    let mut current: Option<(String, String)> = None;
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        match (parse_line(line), current.as_mut()) {
+            (Line::Begin(_, name), None) => {
+                if name.is_empty() {
+                    return Err(format!(
+                        "Protected region at line {} has no name",
+                        index + 1
+                    ));
+                }
+                if result.iter().any(|(n, _)| n == &name) {
+                    return Err(format!("Protected region `{}` is duplicated", name));
+                }
+                current = Some((name, String::new()));
+            }
+            (Line::Begin(_, name), Some((current, _))) => {
+                return Err(format!(
+                    "Protected region `{}` starts inside region `{}` at line {}",
+                    name,
+                    current,
+                    index + 1
+                ))
+            }
+            (Line::End(_), None) => {
+                return Err(format!(
+                    "Protected region end at line {} has no beginning",
+                    index + 1
+                ))
+            }
+            (Line::End(_), Some(_)) => result.push(current.take().unwrap()),
+            (Line::Content(line), Some((_, body))) => body.push_str(line),
+            (Line::Content(_), None) => {}
+        }
+    }
+    if let Some((name, _)) = current {
+        return Err(format!("Protected region `{}` is not closed", name));
+    }
+    Ok(result)
+}
+
+pub fn preserve_regions(previous: &str, generated: &str) -> Result<String, String> {
+    let previous = find_regions(previous)?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let found = find_regions(generated)?;
+    for name in previous.keys() {
+        if !found.iter().any(|(n, _)| n == name) {
+            return Err(format!(
+                "Protected region `{}` disappeared from generated output",
+                name
+            ));
+        }
+    }
+    let mut result = String::with_capacity(generated.len());
+    let mut current: Option<&str> = None;
+    for line in generated.split_inclusive('\n') {
+        match parse_line(line) {
+            Line::Begin(line, name) => {
+                result.push_str(line);
+                if let Some((name, body)) = previous.get_key_value(&name) {
+                    result.push_str(body);
+                    current = Some(name);
+                }
+            }
+            Line::End(line) => {
+                current = None;
+                result.push_str(line);
+            }
+            Line::Content(line) => {
+                if current.is_none() {
+                    result.push_str(line);
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_regions_collects_named_bodies() {
+        let content = "a\n// chrobry:begin first\nx\ny\n// chrobry:end\nb\n# chrobry:begin second\n# chrobry:end\n";
+        assert_eq!(
+            find_regions(content).unwrap(),
+            vec![
+                ("first".to_owned(), "x\ny\n".to_owned()),
+                ("second".to_owned(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn find_regions_reports_malformed_regions() {
+        let cases = [
+            (
+                "// chrobry:begin\n// chrobry:end\n",
+                "Protected region at line 1 has no name",
+            ),
+            (
+                "// chrobry:begin a\n// chrobry:end\n// chrobry:begin a\n// chrobry:end\n",
+                "Protected region `a` is duplicated",
+            ),
+            (
+                "// chrobry:begin a\n// chrobry:begin b\n",
+                "Protected region `b` starts inside region `a` at line 2",
+            ),
+            (
+                "x\n// chrobry:end\n",
+                "Protected region end at line 2 has no beginning",
+            ),
+            (
+                "// chrobry:begin a\nx\n",
+                "Protected region `a` is not closed",
+            ),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(find_regions(content).unwrap_err(), expected);
+        }
+    }
+
+    #[test]
+    fn preserve_regions_carries_over_previous_bodies() {
+        let previous = "old\n// chrobry:begin custom\nkept();\n// chrobry:end\n";
+        let generated = "new\n// chrobry:begin custom\ndefault();\n// chrobry:end\n// chrobry:begin other\nfresh();\n// chrobry:end\n";
+        assert_eq!(
+            preserve_regions(previous, generated).unwrap(),
+            "new\n// chrobry:begin custom\nkept();\n// chrobry:end\n// chrobry:begin other\nfresh();\n// chrobry:end\n"
+        );
+    }
+
+    #[test]
+    fn preserve_regions_rejects_disappeared_region() {
+        let previous = "// chrobry:begin custom\nkept();\n// chrobry:end\n";
+        assert_eq!(
+            preserve_regions(previous, "generated\n").unwrap_err(),
+            "Protected region `custom` disappeared from generated output"
+        );
+    }
+}
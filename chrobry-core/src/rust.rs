@@ -0,0 +1,299 @@
+use crate::ast::{Ast, AstEnum, AstEnumField, AstField, AstStruct, AstTag, AstType, AstValue};
+use quote::ToTokens;
+use std::collections::BTreeMap;
+use syn::{Attribute, Expr, Fields, GenericArgument, Item, Lit, PathArguments, Type};
+
+pub const TAGS_ATTRIBUTE: &str = "chrobry";
+
+pub fn import_rust(content: &str) -> Result<Ast, String> {
+    let file = syn::parse_file(content).map_err(format_error)?;
+    let mut ast = Ast::default();
+    import_items(&file.items, &mut ast)?;
+    let declared = ast
+        .structs
+        .iter()
+        .map(|s| s.name.to_owned())
+        .chain(ast.enums.iter().map(|e| e.name.to_owned()))
+        .collect::<Vec<_>>();
+    for struct_ in &mut ast.structs {
+        for field in &mut struct_.fields {
+            if let AstType::Extern(name) = &field.type_ {
+                if declared.contains(name) {
+                    field.type_ = AstType::Local(name.to_owned());
+                }
+            }
+        }
+    }
+    Ok(ast)
+}
+
+fn format_error(error: syn::Error) -> String {
+    let start = error.span().start();
+    format!("{}:{}: {}", start.line, start.column + 1, error)
+}
+
+fn import_items(items: &[Item], ast: &mut Ast) -> Result<(), String> {
+    for item in items {
+        match item {
+            Item::Struct(item) => {
+                let fields = match &item.fields {
+                    Fields::Named(fields) => fields.named.iter().collect::<Vec<_>>(),
+                    Fields::Unnamed(fields) => fields.unnamed.iter().collect::<Vec<_>>(),
+                    Fields::Unit => vec![],
+                };
+                let fields = fields
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, field)| {
+                        Ok(AstField {
+                            tags: import_tags(&field.attrs)?,
+                            name: match &field.ident {
+                                Some(ident) => ident.to_string(),
+                                None => index.to_string(),
+                            },
+                            type_: AstType::Extern(render_type(&field.ty)),
+                            default: None,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                ast.structs.push(AstStruct {
+                    tags: import_tags(&item.attrs)?,
+                    name: item.ident.to_string(),
+                    base: None,
+                    fields,
+                });
+            }
+            Item::Enum(item) => {
+                let fields = item
+                    .variants
+                    .iter()
+                    .map(|variant| {
+                        Ok(AstEnumField {
+                            tags: import_tags(&variant.attrs)?,
+                            name: variant.ident.to_string(),
+                        })
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                ast.enums.push(AstEnum {
+                    tags: import_tags(&item.attrs)?,
+                    name: item.ident.to_string(),
+                    fields,
+                });
+            }
+            Item::Mod(item) => {
+                if let Some((_, items)) = &item.content {
+                    import_items(items, ast)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn import_tags(attributes: &[Attribute]) -> Result<Vec<AstTag>, String> {
+    let mut result = vec![];
+    for attribute in attributes {
+        if !attribute.path().is_ident(TAGS_ATTRIBUTE) {
+            continue;
+        }
+        attribute
+            .parse_nested_meta(|meta| {
+                let name = meta.path.to_token_stream().to_string();
+                let mut params = BTreeMap::new();
+                if !meta.input.is_empty() && !meta.input.peek(syn::Token![,]) {
+                    meta.parse_nested_meta(|param| {
+                        let key = param.path.to_token_stream().to_string();
+                        let value = if param.input.peek(syn::Token![=]) {
+                            import_value(&param.value()?.parse::<Lit>()?)
+                        } else {
+                            AstValue::default()
+                        };
+                        params.insert(key, value);
+                        Ok(())
+                    })?;
+                }
+                result.push((name, params));
+                Ok(())
+            })
+            .map_err(format_error)?;
+    }
+    Ok(result)
+}
+
+fn import_value(literal: &Lit) -> AstValue {
+    match literal {
+        Lit::Str(value) => AstValue::String(value.value()),
+        Lit::Int(value) => AstValue::Number(value.base10_digits().to_owned()),
+        Lit::Float(value) => AstValue::Number(value.base10_digits().to_owned()),
+        Lit::Bool(value) => AstValue::Bool(value.value),
+        Lit::Char(value) => AstValue::String(value.value().to_string()),
+        literal => AstValue::String(literal.to_token_stream().to_string()),
+    }
+}
+
+fn render_type(type_: &Type) -> String {
+    match type_ {
+        Type::Path(type_) if type_.qself.is_none() => type_
+            .path
+            .segments
+            .iter()
+            .map(|segment| match &segment.arguments {
+                PathArguments::AngleBracketed(arguments) => {
+                    let arguments = arguments
+                        .args
+                        .iter()
+                        .filter_map(|argument| match argument {
+                            GenericArgument::Lifetime(lifetime) => Some(lifetime.to_string()),
+                            GenericArgument::Type(type_) => Some(render_type(type_)),
+                            GenericArgument::Const(expr) => Some(render_expr(expr)),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+                    if arguments.is_empty() {
+                        segment.ident.to_string()
+                    } else {
+                        format!("{}<{}>", segment.ident, arguments.join(", "))
+                    }
+                }
+                _ => segment.ident.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("::"),
+        Type::Reference(type_) => format!(
+            "&{}{}{}",
+            type_
+                .lifetime
+                .as_ref()
+                .map(|lifetime| format!("{} ", lifetime))
+                .unwrap_or_default(),
+            if type_.mutability.is_some() {
+                "mut "
+            } else {
+                ""
+            },
+            render_type(&type_.elem)
+        ),
+        Type::Array(type_) => format!(
+            "[{}; {}]",
+            render_type(&type_.elem),
+            render_expr(&type_.len)
+        ),
+        Type::Slice(type_) => format!("[{}]", render_type(&type_.elem)),
+        Type::Tuple(type_) => format!(
+            "({})",
+            type_
+                .elems
+                .iter()
+                .map(render_type)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Type::Paren(type_) => render_type(&type_.elem),
+        Type::Group(type_) => render_type(&type_.elem),
+        type_ => type_.to_token_stream().to_string(),
+    }
+}
+
+fn render_expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Lit(expr) => match &expr.lit {
+            Lit::Int(value) => value.base10_digits().to_owned(),
+            literal => literal.to_token_stream().to_string(),
+        },
+        expr => expr.to_token_stream().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_rust_builds_types() {
+        let ast = import_rust(
+            r#"
+            #[derive(Debug)]
+            #[chrobry(Display, Describe(inherit = "Base", size = 4, packed))]
+            pub struct Person<'a> {
+                #[chrobry(Column(name = "full_name", index = 1, primary = true))]
+                pub name: &'a str,
+                pub kind: Kind,
+                pub friends: Vec<Option<Person<'a>>>,
+                pub grid: [[u8; 3]; 2],
+            }
+
+            mod inner {
+                #[chrobry(Clone)]
+                pub struct Point(pub f32, #[chrobry(Unit)] f32);
+
+                pub struct Marker;
+            }
+
+            pub enum Kind {
+                #[chrobry(Default)]
+                Adult,
+                Child(u8),
+            }
+            "#,
+        )
+        .unwrap();
+        let names = ast
+            .structs
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Person", "Point", "Marker"]);
+        let person = &ast.structs[0];
+        assert_eq!(person.tags.len(), 2);
+        assert_eq!(person.tags[0], ("Display".to_owned(), BTreeMap::new()));
+        let (name, params) = &person.tags[1];
+        assert_eq!(name, "Describe");
+        assert_eq!(params["inherit"], AstValue::from("Base"));
+        assert_eq!(params["size"], AstValue::Number("4".to_owned()));
+        assert_eq!(params["packed"], AstValue::default());
+        let column = &person.fields[0].tags[0];
+        assert_eq!(column.0, "Column");
+        assert_eq!(column.1["name"], AstValue::from("full_name"));
+        assert_eq!(column.1["index"], AstValue::Number("1".to_owned()));
+        assert_eq!(column.1["primary"], AstValue::Bool(true));
+        let types = person
+            .fields
+            .iter()
+            .map(|f| f.type_.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                AstType::Extern("&'a str".to_owned()),
+                AstType::Local("Kind".to_owned()),
+                AstType::Extern("Vec<Option<Person<'a>>>".to_owned()),
+                AstType::Extern("[[u8; 3]; 2]".to_owned()),
+            ]
+        );
+        let point = &ast.structs[1];
+        assert_eq!(point.tags[0].0, "Clone");
+        let fields = point
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.type_.to_string(), f.tags.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![("0", "f32".to_owned(), 0), ("1", "f32".to_owned(), 1)]
+        );
+        assert!(ast.structs[2].fields.is_empty());
+        let kind = &ast.enums[0];
+        assert_eq!(kind.name, "Kind");
+        assert_eq!(kind.fields[0].tags[0].0, "Default");
+        assert_eq!(kind.fields[1].name, "Child");
+    }
+
+    #[test]
+    fn import_rust_reports_errors_with_location() {
+        let error = import_rust("struct Foo {\n    a: ,\n}\n").unwrap_err();
+        assert!(error.starts_with("2:8: "), "{}", error);
+        let error = import_rust("#[chrobry(Display(name = Foo))]\nstruct Foo;\n").unwrap_err();
+        assert!(error.starts_with("1:26: "), "{}", error);
+    }
+}
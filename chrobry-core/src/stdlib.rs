@@ -0,0 +1,150 @@
+use std::fs::read_to_string;
+
+pub const STD_PREFIX: &str = "std:";
+
+pub const STD_TEMPLATES: &[(&str, &str)] = &[
+    ("cpp/describe", include_str!("../std/cpp/describe.chrobry")),
+    ("cpp/display", include_str!("../std/cpp/display.chrobry")),
+    ("cpp/eq", include_str!("../std/cpp/eq.chrobry")),
+    ("cpp/hash", include_str!("../std/cpp/hash.chrobry")),
+    ("cpp/json", include_str!("../std/cpp/json.chrobry")),
+    (
+        "rust/describe",
+        include_str!("../std/rust/describe.chrobry"),
+    ),
+    ("rust/display", include_str!("../std/rust/display.chrobry")),
+    ("rust/debug", include_str!("../std/rust/debug.chrobry")),
+    ("rust/eq", include_str!("../std/rust/eq.chrobry")),
+    ("rust/hash", include_str!("../std/rust/hash.chrobry")),
+    ("rust/serde", include_str!("../std/rust/serde.chrobry")),
+    (
+        "typescript/describe",
+        include_str!("../std/typescript/describe.chrobry"),
+    ),
+    (
+        "typescript/display",
+        include_str!("../std/typescript/display.chrobry"),
+    ),
+    (
+        "typescript/eq",
+        include_str!("../std/typescript/eq.chrobry"),
+    ),
+    (
+        "typescript/hash",
+        include_str!("../std/typescript/hash.chrobry"),
+    ),
+    (
+        "typescript/json",
+        include_str!("../std/typescript/json.chrobry"),
+    ),
+    (
+        "csharp/describe",
+        include_str!("../std/csharp/describe.chrobry"),
+    ),
+    (
+        "csharp/display",
+        include_str!("../std/csharp/display.chrobry"),
+    ),
+    ("csharp/eq", include_str!("../std/csharp/eq.chrobry")),
+    ("csharp/hash", include_str!("../std/csharp/hash.chrobry")),
+    ("csharp/json", include_str!("../std/csharp/json.chrobry")),
+];
+
+pub fn std_template(path: &str) -> Option<&'static str> {
+    let name = path.strip_prefix(STD_PREFIX)?;
+    STD_TEMPLATES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, content)| *content)
+}
+
+pub fn with_std<F>(mut on_import: F) -> impl FnMut(&str) -> Result<String, String>
+where
+    F: FnMut(&str) -> Result<String, String>,
+{
+    move |path| {
+        if !path.starts_with(STD_PREFIX) {
+            return on_import(path);
+        }
+        match std_template(path) {
+            Some(content) => Ok(content.to_owned()),
+            None => Err(format!("Standard template `{}` does not exist", path)),
+        }
+    }
+}
+
+pub fn default_on_import(path: &str) -> Result<String, String> {
+    with_std(|path| {
+        read_to_string(path)
+            .map_err(|error| format!("Could not open imported file `{}`: {}", path, error))
+    })(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{check, generate, processor::Options};
+    use std::collections::HashMap;
+
+    fn no_import(path: &str) -> Result<String, String> {
+        Err(format!("Unexpected import `{}`", path))
+    }
+
+    #[test]
+    fn std_templates_generate_code() {
+        for (name, _) in STD_TEMPLATES {
+            let (_, behaviour) = name.split_once('/').unwrap();
+            let behaviour = behaviour[..1].to_uppercase() + &behaviour[1..];
+            let content = format!(
+                "import 'std:{name}'\n\n\
+                 @{behaviour}\nenum Kind {{\n  Adult\n  Child\n}}\n\n\
+                 @{behaviour}\nstruct Base {{\n  id: 'string'\n}}\n\n\
+                 @{behaviour}\nstruct Person : Base {{\n  kind: Kind\n  name: 'string'\n}}\n",
+                name = name,
+                behaviour = behaviour
+            );
+            let warnings = check(&content, &Options::default(), &[], with_std(no_import));
+            assert_eq!(warnings, Ok(vec![]), "{}", name);
+            let output = generate(&content, "\n", HashMap::new(), with_std(no_import))
+                .unwrap_or_else(|error| panic!("{}: {}", name, error));
+            assert!(output.contains("Person"), "{}", name);
+        }
+    }
+
+    #[test]
+    fn with_std_resolves_std_paths_only() {
+        let mut on_import = with_std(|path| Ok(format!("read {}", path)));
+        assert_eq!(
+            on_import("std:rust/debug").unwrap(),
+            std_template("std:rust/debug").unwrap()
+        );
+        assert_eq!(
+            on_import("./common.chrobry").unwrap(),
+            "read ./common.chrobry"
+        );
+        assert_eq!(std_template("rust/debug"), None);
+    }
+
+    #[test]
+    fn std_imports_reject_unknown_names() {
+        assert_eq!(
+            with_std(no_import)("std:rust/missing").unwrap_err(),
+            "Standard template `std:rust/missing` does not exist"
+        );
+        assert_eq!(
+            default_on_import("std:cobol/describe").unwrap_err(),
+            "Standard template `std:cobol/describe` does not exist"
+        );
+    }
+
+    #[test]
+    fn default_on_import_reads_files() {
+        assert_eq!(
+            default_on_import("std:cpp/eq").unwrap(),
+            std_template("std:cpp/eq").unwrap()
+        );
+        assert!(default_on_import("./missing.chrobry")
+            .unwrap_err()
+            .starts_with("Could not open imported file `./missing.chrobry`: "));
+    }
+}